@@ -0,0 +1,203 @@
+//! Mod for grid cellular automata shared between AoC puzzles
+#![allow(dead_code)]
+
+use ndarray::prelude::*;
+
+/// Cells counted as adjacent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Neighbourhood {
+    /// Orthogonal neighbours only
+    VonNeumann,
+    /// Orthogonal and diagonal neighbours
+    Moore,
+}
+
+impl Neighbourhood {
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Neighbourhood::VonNeumann => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighbourhood::Moore => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+/// What lies past the edge of the grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum EdgePolicy {
+    /// Nothing, edge cells simply have fewer neighbours
+    Bounded,
+    /// The opposite edge, the grid wraps around like a torus
+    Toroidal,
+}
+
+/// How cells evolve over one step. Every cell is incremented, then cells past
+/// the threshold fire, each at most once, propagating to their neighbours until
+/// no new cell fires. Finally every cell is reset, knowing whether it fired.
+pub(crate) trait Rule {
+    type Cell;
+
+    fn increment(&self, cell: &mut Self::Cell);
+    fn threshold(&self, cell: &Self::Cell) -> bool;
+    fn propagate(&self, cell: &mut Self::Cell);
+    fn reset(&self, cell: &mut Self::Cell, fired: bool);
+}
+
+/// A rule along with the grid topology it runs on
+#[derive(Clone, Debug)]
+pub(crate) struct Automaton<R: Rule> {
+    pub(crate) rule: R,
+    pub(crate) neighbourhood: Neighbourhood,
+    pub(crate) edges: EdgePolicy,
+}
+
+impl<R: Rule> Automaton<R> {
+    pub(crate) fn new(rule: R, neighbourhood: Neighbourhood, edges: EdgePolicy) -> Automaton<R> {
+        Automaton {
+            rule,
+            neighbourhood,
+            edges,
+        }
+    }
+
+    /// Neighbours of `(y, x)` in a grid of shape `(m, n)`
+    pub(crate) fn neighbours(
+        &self,
+        (m, n): (usize, usize),
+        (y, x): (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbourhood
+            .offsets()
+            .iter()
+            .filter_map(move |&(dy, dx)| match self.edges {
+                EdgePolicy::Bounded => {
+                    let ny = y.checked_add_signed(dy).filter(|&ny| ny < m)?;
+                    let nx = x.checked_add_signed(dx).filter(|&nx| nx < n)?;
+                    Some((ny, nx))
+                }
+                EdgePolicy::Toroidal => Some((
+                    (y as isize + dy).rem_euclid(m as isize) as usize,
+                    (x as isize + dx).rem_euclid(n as isize) as usize,
+                )),
+            })
+    }
+
+    /// Advance the grid one step, giving which cells fired
+    pub(crate) fn step(&self, cells: &mut Array2<R::Cell>) -> Array2<bool> {
+        let dim = cells.dim();
+        cells.iter_mut().for_each(|c| self.rule.increment(c));
+
+        let mut fired = Array2::from_elem(dim, false);
+        loop {
+            let firing: Vec<(usize, usize)> = cells
+                .indexed_iter()
+                .filter(|&(idx, c)| !fired[idx] && self.rule.threshold(c))
+                .map(|(idx, _)| idx)
+                .collect();
+            if firing.is_empty() {
+                break;
+            }
+
+            for idx in firing {
+                fired[idx] = true;
+                for adj in self.neighbours(dim, idx) {
+                    self.rule.propagate(&mut cells[adj]);
+                }
+            }
+        }
+
+        for (c, &f) in cells.iter_mut().zip(fired.iter()) {
+            self.rule.reset(c, f);
+        }
+        fired
+    }
+}
+
+/// Conway's Game of Life. Live cells fire to count themselves into their
+/// neighbours, and the count decides each cell's fate on reset.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GameOfLife;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LifeCell {
+    pub(crate) alive: bool,
+    live_neighbours: u8,
+}
+
+impl LifeCell {
+    pub(crate) fn new(alive: bool) -> LifeCell {
+        LifeCell {
+            alive,
+            live_neighbours: 0,
+        }
+    }
+}
+
+impl Rule for GameOfLife {
+    type Cell = LifeCell;
+
+    fn increment(&self, cell: &mut LifeCell) {
+        cell.live_neighbours = 0;
+    }
+
+    fn threshold(&self, cell: &LifeCell) -> bool {
+        cell.alive
+    }
+
+    fn propagate(&self, cell: &mut LifeCell) {
+        cell.live_neighbours += 1;
+    }
+
+    fn reset(&self, cell: &mut LifeCell, _fired: bool) {
+        cell.alive = matches!((cell.alive, cell.live_neighbours), (_, 3) | (true, 2));
+    }
+}
+
+fn life_grid(rows: &[&str]) -> Array2<bool> {
+    let cells: Vec<bool> = rows
+        .iter()
+        .flat_map(|r| r.chars().map(|c| c == '#'))
+        .collect();
+    Array2::from_shape_vec((rows.len(), rows[0].len()), cells).unwrap()
+}
+
+#[test]
+fn test_automaton_neighbours() {
+    let bounded = Automaton::new(GameOfLife, Neighbourhood::Moore, EdgePolicy::Bounded);
+    assert_eq!(bounded.neighbours((3, 3), (0, 0)).count(), 3);
+    assert_eq!(bounded.neighbours((3, 3), (1, 1)).count(), 8);
+
+    let torus = Automaton::new(GameOfLife, Neighbourhood::VonNeumann, EdgePolicy::Toroidal);
+    let adj: Vec<(usize, usize)> = torus.neighbours((3, 4), (0, 0)).collect();
+    assert_eq!(adj, vec![(2, 0), (0, 3), (0, 1), (1, 0)]);
+}
+
+#[test]
+fn test_automaton_game_of_life() {
+    let life = Automaton::new(GameOfLife, Neighbourhood::Moore, EdgePolicy::Bounded);
+    let mut blinker =
+        life_grid(&[".....", "..#..", "..#..", "..#..", "....."]).map(|&a| LifeCell::new(a));
+    life.step(&mut blinker);
+    assert_eq!(
+        blinker.map(|c| c.alive),
+        life_grid(&[".....", ".....", ".###.", ".....", "....."])
+    );
+
+    // A glider crosses a 6x6 torus back to where it started in 24 steps
+    let torus = Automaton::new(GameOfLife, Neighbourhood::Moore, EdgePolicy::Toroidal);
+    let start = life_grid(&[".#....", "..#...", "###...", "......", "......", "......"]);
+    let mut glider = start.map(|&a| LifeCell::new(a));
+    for _ in 0..24 {
+        torus.step(&mut glider);
+        assert_eq!(glider.iter().filter(|c| c.alive).count(), 5);
+    }
+    assert_eq!(glider.map(|c| c.alive), start);
+}
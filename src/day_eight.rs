@@ -2,7 +2,6 @@
 //! Day Eight - Seven Segment Search
 #![allow(dead_code)]
 
-use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
 use crate::read_input;
@@ -19,145 +18,418 @@ pub(crate) fn day_eight_main() {
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
+/// Display segments, in the order used for bit positions of segment masks
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Segment {
+    UpTop,
+    UpLeft,
+    UpRight,
+    Mid,
+    DnLeft,
+    DnRight,
+    DnBottom,
+}
+
+const SEGMENTS: [Segment; 7] = [
+    Segment::UpTop,
+    Segment::UpLeft,
+    Segment::UpRight,
+    Segment::Mid,
+    Segment::DnLeft,
+    Segment::DnRight,
+    Segment::DnBottom,
+];
+
+/// Segment masks lit for digits 0 through 9
+const DECIMAL: [(char, u8); 10] = [
+    ('0', 0b1110111),
+    ('1', 0b0100100),
+    ('2', 0b1011101),
+    ('3', 0b1101101),
+    ('4', 0b0101110),
+    ('5', 0b1101011),
+    ('6', 0b1111011),
+    ('7', 0b0100101),
+    ('8', 0b1111111),
+    ('9', 0b1101111),
+];
+
+/// Segment masks lit for the hex digits past 9, drawn as `A b C d E F`
+const HEX_LETTERS: [(char, u8); 6] = [
+    ('A', 0b0111111),
+    ('B', 0b1111010),
+    ('C', 0b1010011),
+    ('D', 0b1111100),
+    ('E', 0b1011011),
+    ('F', 0b0011011),
+];
+
+const ALL_SEGMENTS: u8 = 0b1111111;
+
+/// Symbols a display can show and the segments lit for each, in order of
+/// their numeric value
+#[derive(Clone, Debug, PartialEq)]
+struct Alphabet {
+    glyphs: Vec<(char, u8)>,
+}
+
+impl Alphabet {
+    /// Build an alphabet from symbol and segment mask pairs. Symbols and masks
+    /// must be unique, and masks may only use the seven segment bits.
+    fn new(glyphs: &[(char, u8)]) -> Option<Alphabet> {
+        for (i, &(sym, mask)) in glyphs.iter().enumerate() {
+            if mask & !ALL_SEGMENTS != 0 || glyphs[..i].iter().any(|&(s, m)| s == sym || m == mask)
+            {
+                return None;
+            }
+        }
+
+        Some(Alphabet {
+            glyphs: glyphs.to_vec(),
+        })
+    }
+
+    fn decimal() -> Alphabet {
+        Alphabet::new(&DECIMAL).unwrap()
+    }
+
+    fn hexadecimal() -> Alphabet {
+        let glyphs: Vec<(char, u8)> = DECIMAL.iter().chain(&HEX_LETTERS).copied().collect();
+        Alphabet::new(&glyphs).unwrap()
+    }
+
+    /// Position of the glyph lighting exactly `segments`
+    fn index_of(&self, segments: u8) -> Option<usize> {
+        self.glyphs.iter().position(|&(_, m)| m == segments)
+    }
+
+    fn masks(&self) -> impl Iterator<Item = u8> + '_ {
+        self.glyphs.iter().map(|&(_, m)| m)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum WiringError {
+    /// A pattern used something other than wires `a` through `g`
+    InvalidPattern(String),
+    /// No wiring turns every pattern into a distinct digit
+    Inconsistent,
+    /// More than one wiring fits the patterns
+    Ambiguous,
+    /// An output pattern lit segments matching no glyph of the alphabet
+    UnknownGlyph(String),
+    /// The decoded value does not fit in a `u128`
+    Overflow,
+}
+
+/// Bitmask of the wires `a` through `g` lit in a pattern
+fn wire_mask(pat: &str) -> Result<u8, WiringError> {
+    pat.chars().try_fold(0_u8, |mask, c| match c {
+        'a'..='g' => Ok(mask | 1 << (c as u8 - b'a')),
+        _ => Err(WiringError::InvalidPattern(pat.to_string())),
+    })
+}
+
 #[derive(Debug)]
 struct SevenSegment {
-    up_top: char,
-    up_left: char,
-    up_right: char,
-    mid: char,
-    dn_left: char,
-    dn_right: char,
-    dn_bottom: char,
+    /// Segment index driven by each wire, `a` first
+    wiring: [u8; 7],
+    alphabet: Alphabet,
 }
 
 impl SevenSegment {
     /// Identifies the seven segment display partitions given
     ///  a series of input patterns.
-    fn new(seq: Vec<&str>) -> SevenSegment {
-        let mut five_segs: Vec<HashSet<char>> = Vec::new();
-        let mut six_segs: Vec<HashSet<char>> = Vec::new();
-        let mut unique_map: HashMap<usize, HashSet<char>> = HashMap::new();
-        for s in seq {
-            match s.len() {
-                2 | 3 | 4 | 7 => {
-                    unique_map.insert(s.len(), s.chars().collect());
-                }
-                5 => five_segs.push(s.chars().collect()),
-                6 => six_segs.push(s.chars().collect()),
-                _ => (),
-            }
+    fn new(seq: Vec<&str>) -> Result<SevenSegment, WiringError> {
+        SevenSegment::with_alphabet(seq, Alphabet::decimal())
+    }
+
+    /// Identifies the display partitions for a display drawing `alphabet`
+    fn with_alphabet(seq: Vec<&str>, alphabet: Alphabet) -> Result<SevenSegment, WiringError> {
+        let mut patterns = seq
+            .iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| wire_mask(s))
+            .collect::<Result<Vec<u8>, WiringError>>()?;
+        patterns.sort_unstable();
+        patterns.dedup();
+
+        let candidates = propagate(&patterns, &alphabet).ok_or(WiringError::Inconsistent)?;
+
+        let mut solutions = Vec::new();
+        search(
+            &patterns,
+            &alphabet,
+            &candidates,
+            &mut [0; 7],
+            0,
+            0,
+            &mut solutions,
+        );
+        match solutions.len() {
+            0 => Err(WiringError::Inconsistent),
+            1 => Ok(SevenSegment {
+                wiring: solutions[0],
+                alphabet,
+            }),
+            _ => Err(WiringError::Ambiguous),
         }
+    }
 
-        let one = unique_map.get(&2).unwrap().clone();
-        let four = unique_map.get(&4).unwrap().clone();
-        let seven = unique_map.get(&3).unwrap().clone();
-        let eight = unique_map.get(&7).unwrap().clone();
-
-        // UP_TOP
-        let up_top = *seven.difference(&one).next().unwrap();
-
-        // DN_BOTTOM
-        let mut jig_dn_bottom = four.clone();
-        jig_dn_bottom.insert(up_top);
-        let mut dn_bottom = '1';
-        for seq in &six_segs {
-            let a: HashSet<&char> = seq.difference(&jig_dn_bottom).collect();
-            if a.len() == 1 {
-                dn_bottom = **a.iter().next().unwrap();
-            }
+    /// Wire driving each segment
+    fn mapping(&self) -> Vec<(char, Segment)> {
+        self.wiring
+            .iter()
+            .enumerate()
+            .map(|(w, &seg)| ((b'a' + w as u8) as char, SEGMENTS[seg as usize]))
+            .collect()
+    }
+
+    /// Wire driving a given segment
+    fn wire(&self, segment: Segment) -> char {
+        let seg = segment as u8;
+        let w = self.wiring.iter().position(|&s| s == seg).unwrap();
+        (b'a' + w as u8) as char
+    }
+
+    /// Translate a mask of wires into the mask of segments they light
+    fn segment_mask(&self, wires: u8) -> u8 {
+        translate(&self.wiring, wires)
+    }
+
+    /// Decode all encoded patterns for a given input into their symbols
+    fn decode(&self, seq: Vec<&str>) -> Result<String, WiringError> {
+        seq.iter().map(|s| self.match_pattern(s)).collect()
+    }
+
+    /// Decode all encoded patterns for a given input to yield a numerical
+    /// value, reading each glyph's position in the alphabet as a digit in base
+    /// of the alphabet's size
+    fn decode_value(&self, seq: Vec<&str>) -> Result<u128, WiringError> {
+        let radix = self.alphabet.glyphs.len() as u128;
+        seq.iter().try_fold(0_u128, |acc, s| {
+            let digit = self.match_index(s)? as u128;
+            acc.checked_mul(radix)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or(WiringError::Overflow)
+        })
+    }
+
+    /// Match an individual, encoded output pattern
+    fn match_pattern(&self, pat: &str) -> Result<char, WiringError> {
+        let index = self.match_index(pat)?;
+        Ok(self.alphabet.glyphs[index].0)
+    }
+
+    /// Position in the alphabet of an individual, encoded output pattern
+    fn match_index(&self, pat: &str) -> Result<usize, WiringError> {
+        let segments = self.segment_mask(wire_mask(pat)?);
+        self.alphabet
+            .index_of(segments)
+            .ok_or_else(|| WiringError::UnknownGlyph(pat.to_string()))
+    }
+}
+
+fn translate(wiring: &[u8; 7], wires: u8) -> u8 {
+    (0..7)
+        .filter(|w| wires & 1 << w != 0)
+        .fold(0, |mask, w| mask | 1 << wiring[w])
+}
+
+/// Narrow the segments each wire may drive until nothing changes. Wires lit in
+/// a pattern may only drive segments some same-sized glyph lights, unlit wires
+/// only segments some same-sized glyph leaves dark, and a wire pinned to one
+/// segment rules it out for the rest.
+fn propagate(patterns: &[u8], alphabet: &Alphabet) -> Option<[u8; 7]> {
+    let mut candidates = [ALL_SEGMENTS; 7];
+
+    for &pat in patterns {
+        let size = pat.count_ones();
+        let lit = alphabet
+            .masks()
+            .filter(|g| g.count_ones() == size)
+            .fold(0, |acc, g| acc | g);
+        let dark = alphabet
+            .masks()
+            .filter(|g| g.count_ones() == size)
+            .fold(0, |acc, g| acc | (!g & ALL_SEGMENTS));
+
+        for (w, cand) in candidates.iter_mut().enumerate() {
+            *cand &= if pat & 1 << w != 0 { lit } else { dark };
         }
+    }
 
-        // DN_LEFT
-        let mut jig_dn_left = four;
-        jig_dn_left.insert(up_top);
-        jig_dn_left.insert(dn_bottom);
-        let dn_left = *eight.difference(&jig_dn_left).next().unwrap();
-
-        // UP_LEFT
-        let mut jig_up_left = seven.clone();
-        jig_up_left.insert(dn_bottom);
-        jig_up_left.insert(dn_left);
-        let mut up_left = '1';
-        for seq in &six_segs {
-            let a: HashSet<&char> = seq.difference(&jig_up_left).collect();
-            if a.len() == 1 {
-                up_left = **a.iter().next().unwrap();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for w in 0..7 {
+            match candidates[w].count_ones() {
+                0 => return None,
+                1 => {
+                    let pinned = candidates[w];
+                    for (other, cand) in candidates.iter_mut().enumerate() {
+                        if other != w && *cand & pinned != 0 {
+                            *cand &= !pinned;
+                            changed = true;
+                        }
+                    }
+                }
+                _ => (),
             }
         }
+    }
 
-        // MID
-        let mut jig_mid = seven;
-        jig_mid.insert(up_left);
-        jig_mid.insert(dn_left);
-        jig_mid.insert(dn_bottom);
-        let mid = *eight.difference(&jig_mid).next().unwrap();
-
-        // DN_RIGHT
-        let mut jig_down_right = HashSet::new();
-        jig_down_right.insert(up_top);
-        jig_down_right.insert(up_left);
-        jig_down_right.insert(mid);
-        jig_down_right.insert(dn_left);
-        jig_down_right.insert(dn_bottom);
-        let mut dn_right = '1';
-        for seq in &six_segs {
-            let a: HashSet<&char> = seq.difference(&jig_down_right).collect();
-            if a.len() == 1 {
-                dn_right = **a.iter().next().unwrap();
-            }
+    Some(candidates)
+}
+
+/// Assign wires in order from their remaining candidates, keeping every full
+/// wiring that maps all patterns onto glyphs. Distinct patterns always land on
+/// distinct glyphs as the wiring is a permutation. Stops after two, which is
+/// enough to call the patterns ambiguous.
+fn search(
+    patterns: &[u8],
+    alphabet: &Alphabet,
+    candidates: &[u8; 7],
+    wiring: &mut [u8; 7],
+    wire: usize,
+    used: u8,
+    solutions: &mut Vec<[u8; 7]>,
+) {
+    if solutions.len() > 1 {
+        return;
+    }
+
+    if wire == 7 {
+        if patterns
+            .iter()
+            .all(|&pat| alphabet.index_of(translate(wiring, pat)).is_some())
+        {
+            solutions.push(*wiring);
         }
+        return;
+    }
 
-        // UP_RIGHT
-        let up_right = *one
-            .difference(&[dn_right].iter().cloned().collect())
-            .next()
-            .unwrap();
-
-        SevenSegment {
-            up_top,
-            up_left,
-            up_right,
-            mid,
-            dn_left,
-            dn_right,
-            dn_bottom,
+    for seg in 0..7 {
+        let bit = 1 << seg;
+        if candidates[wire] & bit != 0 && used & bit == 0 {
+            wiring[wire] = seg;
+            search(
+                patterns,
+                alphabet,
+                candidates,
+                wiring,
+                wire + 1,
+                used | bit,
+                solutions,
+            );
         }
     }
+}
+
+/// Small xorshift generator so scrambled puzzles are reproducible from a seed
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // Spread the seed with a splitmix step, xorshift stalls on zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        XorShift((z ^ (z >> 31)).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
 
-    /// Decode all encoded patterns for a given input to yield a numerical value
-    fn decode(&self, seq: Vec<&str>) -> u16 {
-        let mut output = "".to_string();
+    /// Value in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
 
-        for s in seq {
-            output.push(self.match_pattern(s).unwrap());
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
         }
+    }
+}
 
-        output.parse().unwrap()
+/// A scrambled puzzle line with the answers used to build it
+#[derive(Debug)]
+struct GeneratedLine {
+    line: String,
+    wiring: [u8; 7],
+    value: u128,
+}
+
+/// Builds puzzle lines in the `patterns | outputs` format, each with its own
+/// random wiring
+struct PuzzleGenerator {
+    rng: XorShift,
+    alphabet: Alphabet,
+    width: usize,
+}
+
+impl PuzzleGenerator {
+    fn new(seed: u64, alphabet: Alphabet, width: usize) -> PuzzleGenerator {
+        PuzzleGenerator {
+            rng: XorShift::new(seed),
+            alphabet,
+            width,
+        }
     }
 
-    /// Match an individual, encoded output pattern
-    fn match_pattern(&self, pat: &str) -> Result<char, &str> {
-        match (
-            pat.contains(&self.up_top.to_string().as_str()),
-            pat.contains(&self.up_left.to_string().as_str()),
-            pat.contains(&self.up_right.to_string().as_str()),
-            pat.contains(&self.mid.to_string().as_str()),
-            pat.contains(&self.dn_left.to_string().as_str()),
-            pat.contains(&self.dn_right.to_string().as_str()),
-            pat.contains(&self.dn_bottom.to_string().as_str()),
-        ) {
-            (true, true, true, false, true, true, true) => Ok('0'),
-            (false, false, true, false, false, true, false) => Ok('1'),
-            (true, false, true, true, true, false, true) => Ok('2'),
-            (true, false, true, true, false, true, true) => Ok('3'),
-            (false, true, true, true, false, true, false) => Ok('4'),
-            (true, true, false, true, false, true, true) => Ok('5'),
-            (true, true, false, true, true, true, true) => Ok('6'),
-            (true, false, true, false, false, true, false) => Ok('7'),
-            (true, true, true, true, true, true, true) => Ok('8'),
-            (true, true, true, true, false, true, true) => Ok('9'),
-            _ => Err("Unable to decode"),
+    /// Scramble `value`, zero padded to the output width. Gives `None` if it
+    /// needs more glyphs than the width allows.
+    fn generate(&mut self, value: u128) -> Option<GeneratedLine> {
+        let radix = self.alphabet.glyphs.len() as u128;
+        let mut digits = vec![0_usize; self.width];
+        let mut rest = value;
+        for d in digits.iter_mut().rev() {
+            *d = (rest % radix) as usize;
+            rest /= radix;
         }
+        if rest != 0 {
+            return None;
+        }
+
+        let mut wiring = [0, 1, 2, 3, 4, 5, 6];
+        self.rng.shuffle(&mut wiring);
+
+        let masks: Vec<u8> = self.alphabet.masks().collect();
+        let mut patterns: Vec<String> = masks
+            .into_iter()
+            .map(|mask| self.scramble(&wiring, mask))
+            .collect();
+        self.rng.shuffle(&mut patterns);
+
+        let outputs: Vec<String> = digits
+            .iter()
+            .map(|&d| self.scramble(&wiring, self.alphabet.glyphs[d].1))
+            .collect();
+
+        Some(GeneratedLine {
+            line: format!("{} | {}", patterns.join(" "), outputs.join(" ")),
+            wiring,
+            value,
+        })
+    }
+
+    fn generate_all(&mut self, values: &[u128]) -> Option<Vec<GeneratedLine>> {
+        values.iter().map(|&v| self.generate(v)).collect()
+    }
+
+    /// Wires lighting the segments of `mask`, in random order
+    fn scramble(&mut self, wiring: &[u8; 7], mask: u8) -> String {
+        let mut wires: Vec<char> = (0..7)
+            .filter(|&w| mask & 1 << wiring[w] != 0)
+            .map(|w| (b'a' + w as u8) as char)
+            .collect();
+        self.rng.shuffle(&mut wires);
+        wires.into_iter().collect()
     }
 }
 
@@ -179,16 +451,21 @@ fn part_one(values: &[String]) -> u32 {
     unique_count
 }
 
-fn part_two(values: &[String]) -> u32 {
-    let mut accumulator = 0_u32;
+fn part_two(values: &[String]) -> u64 {
+    let mut accumulator = 0_u64;
 
     for line in values {
         let line_parts: Vec<&str> = line.split(" | ").collect();
         let patterns: Vec<&str> = line_parts[0].split(' ').collect();
         let encoded: Vec<&str> = line_parts[1].split(' ').collect();
 
-        let sevseg = SevenSegment::new(patterns);
-        accumulator += sevseg.decode(encoded) as u32;
+        match SevenSegment::new(patterns) {
+            Ok(sevseg) => match sevseg.decode_value(encoded) {
+                Ok(value) => accumulator += value as u64,
+                Err(err) => println!("Part Two, Skipping {line}: {err:?}"),
+            },
+            Err(err) => println!("Part Two, Skipping {line}: {err:?}"),
+        }
     }
 
     println!("Part Two, Sum: {}", accumulator);
@@ -204,15 +481,15 @@ fn test_dayeight_part_one() {
 #[test]
 fn test_dayeight_part_two() {
     let values = read_input::read_file("day_eight_test_input.txt");
-    let mut accumulator = 0_u32;
+    let mut accumulator = 0_u128;
 
     for line in values {
         let line_parts: Vec<&str> = line.split(" | ").collect();
         let patterns: Vec<&str> = line_parts[0].split(' ').collect();
         let encoded: Vec<&str> = line_parts[1].split(' ').collect();
 
-        let sevseg = SevenSegment::new(patterns);
-        accumulator += sevseg.decode(encoded) as u32;
+        let sevseg = SevenSegment::new(patterns).unwrap();
+        accumulator += sevseg.decode_value(encoded).unwrap();
     }
     assert_eq!(accumulator, 61229);
 }
@@ -222,15 +499,67 @@ fn test_dayeight_sevensegment_new() {
     let seq: Vec<&str> = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
         .split(' ')
         .collect();
-    let sevseg = SevenSegment::new(seq);
-
-    assert!(sevseg.up_top == 'd');
-    assert!(sevseg.up_left == 'e');
-    assert!(sevseg.up_right == 'a');
-    assert!(sevseg.mid == 'f');
-    assert!(sevseg.dn_left == 'g');
-    assert!(sevseg.dn_right == 'b');
-    assert!(sevseg.dn_bottom == 'c');
+    let sevseg = SevenSegment::new(seq).unwrap();
+
+    assert!(sevseg.wire(Segment::UpTop) == 'd');
+    assert!(sevseg.wire(Segment::UpLeft) == 'e');
+    assert!(sevseg.wire(Segment::UpRight) == 'a');
+    assert!(sevseg.wire(Segment::Mid) == 'f');
+    assert!(sevseg.wire(Segment::DnLeft) == 'g');
+    assert!(sevseg.wire(Segment::DnRight) == 'b');
+    assert!(sevseg.wire(Segment::DnBottom) == 'c');
+    assert_eq!(
+        sevseg.mapping(),
+        vec![
+            ('a', Segment::UpRight),
+            ('b', Segment::DnRight),
+            ('c', Segment::DnBottom),
+            ('d', Segment::UpTop),
+            ('e', Segment::UpLeft),
+            ('f', Segment::Mid),
+            ('g', Segment::DnLeft),
+        ]
+    );
+}
+
+#[test]
+fn test_dayeight_sevensegment_new_errors() {
+    // Missing the one and four still pins down the wiring
+    let seq: Vec<&str> = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb cagedb"
+        .split(' ')
+        .collect();
+    assert_eq!(SevenSegment::new(seq).unwrap().wire(Segment::UpTop), 'd');
+
+    // Without the seven as well, two wirings fit
+    let seq: Vec<&str> = "acedgfb cdfbe gcdfa fbcad cefabd cdfgeb cagedb"
+        .split(' ')
+        .collect();
+    assert_eq!(SevenSegment::new(seq).unwrap_err(), WiringError::Ambiguous);
+
+    let seq: Vec<&str> = "ab dab".split(' ').collect();
+    assert_eq!(SevenSegment::new(seq).unwrap_err(), WiringError::Ambiguous);
+
+    // Two different three wire patterns cannot both be a seven
+    let seq: Vec<&str> = "ab dab gab".split(' ').collect();
+    assert_eq!(
+        SevenSegment::new(seq).unwrap_err(),
+        WiringError::Inconsistent
+    );
+
+    let seq: Vec<&str> = "ab abz".split(' ').collect();
+    assert_eq!(
+        SevenSegment::new(seq).unwrap_err(),
+        WiringError::InvalidPattern("abz".to_string())
+    );
+}
+
+#[test]
+fn test_dayeight_part_two_actual() {
+    let input = read_input::read_file("day_eight_input.txt");
+    for line in &input {
+        let patterns: Vec<&str> = line.split(" | ").next().unwrap().split(' ').collect();
+        assert!(SevenSegment::new(patterns).is_ok());
+    }
 }
 
 #[test]
@@ -241,21 +570,90 @@ fn test_dayeight_sevensegment_decode_single() {
     let sig_pat: Vec<&str> = values[0].split(' ').collect();
     let encoded: Vec<&str> = values[1].split(' ').collect();
 
-    let sevseg = SevenSegment::new(sig_pat);
-    assert_eq!(sevseg.decode(encoded), 5353);
+    let sevseg = SevenSegment::new(sig_pat).unwrap();
+    assert_eq!(sevseg.decode(encoded.clone()), Ok("5353".to_string()));
+    assert_eq!(sevseg.decode_value(encoded), Ok(5353));
 }
 
 #[test]
 fn test_dayeight_sevensegment_decode_testinput() {
     let input = read_input::read_file("day_eight_test_input.txt");
-    let exp_vals: Vec<u16> = vec![8394, 9781, 1197, 9361, 4873, 8418, 4548, 1625, 8717, 4315];
+    let exp_vals: Vec<u128> = vec![8394, 9781, 1197, 9361, 4873, 8418, 4548, 1625, 8717, 4315];
 
     for (i, line) in input.iter().enumerate() {
         let line_parts: Vec<&str> = line.split(" | ").collect();
         let patterns: Vec<&str> = line_parts[0].split(' ').collect();
         let encoded: Vec<&str> = line_parts[1].split(' ').collect();
 
-        let sevseg = SevenSegment::new(patterns);
-        assert_eq!(sevseg.decode(encoded), exp_vals[i]);
+        let sevseg = SevenSegment::new(patterns).unwrap();
+        assert_eq!(sevseg.decode_value(encoded), Ok(exp_vals[i]));
     }
 }
+
+#[test]
+fn test_dayeight_alphabet_new() {
+    assert_eq!(Alphabet::decimal().glyphs.len(), 10);
+    assert_eq!(Alphabet::hexadecimal().glyphs.len(), 16);
+    assert!(Alphabet::new(&[('0', 0b1110111), ('O', 0b1110111)]).is_none());
+    assert!(Alphabet::new(&[('0', 0b1110111), ('0', 0b0100100)]).is_none());
+    assert!(Alphabet::new(&[('X', 0b10000000)]).is_none());
+}
+
+#[test]
+fn test_dayeight_sevensegment_decode_hex() {
+    // Same wiring as the puzzle example, now showing hex glyphs
+    let sig_pat: Vec<&str> = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+        .split(' ')
+        .collect();
+    let sevseg = SevenSegment::with_alphabet(sig_pat, Alphabet::hexadecimal()).unwrap();
+
+    // up_top d, up_left e, up_right a, mid f, dn_left g, dn_right b, dn_bottom c
+    let encoded = vec!["deafgb", "efgbc", "degc", "afgbc"];
+    assert_eq!(sevseg.decode(encoded.clone()), Ok("ABCD".to_string()));
+    assert_eq!(sevseg.decode_value(encoded), Ok(0xABCD));
+
+    assert_eq!(
+        sevseg.decode(vec!["ab", "e"]),
+        Err(WiringError::UnknownGlyph("e".to_string()))
+    );
+
+    let too_wide = vec!["acedgfb"; 33];
+    assert_eq!(sevseg.decode_value(too_wide), Err(WiringError::Overflow));
+}
+
+#[test]
+fn test_dayeight_generator_roundtrip() {
+    let values: Vec<u128> = (0..200).map(|i| (i * 7919) % 10_000).collect();
+    let mut generator = PuzzleGenerator::new(2021, Alphabet::decimal(), 4);
+    let generated = generator.generate_all(&values).unwrap();
+
+    for gen in &generated {
+        let line_parts: Vec<&str> = gen.line.split(" | ").collect();
+        let patterns: Vec<&str> = line_parts[0].split(' ').collect();
+        let encoded: Vec<&str> = line_parts[1].split(' ').collect();
+
+        let sevseg = SevenSegment::new(patterns).unwrap();
+        assert_eq!(sevseg.wiring, gen.wiring);
+        assert_eq!(sevseg.decode_value(encoded), Ok(gen.value));
+    }
+
+    let lines: Vec<String> = generated.iter().map(|g| g.line.clone()).collect();
+    assert_eq!(part_two(&lines), values.iter().sum::<u128>() as u64);
+}
+
+#[test]
+fn test_dayeight_generator_seeded() {
+    let mut gen_a = PuzzleGenerator::new(7, Alphabet::hexadecimal(), 4);
+    let mut gen_b = PuzzleGenerator::new(7, Alphabet::hexadecimal(), 4);
+    let a = gen_a.generate(0xBEEF).unwrap();
+    assert_eq!(a.line, gen_b.generate(0xBEEF).unwrap().line);
+    assert_ne!(a.line, gen_a.generate(0xBEEF).unwrap().line);
+
+    let line_parts: Vec<&str> = a.line.split(" | ").collect();
+    let patterns: Vec<&str> = line_parts[0].split(' ').collect();
+    let encoded: Vec<&str> = line_parts[1].split(' ').collect();
+    let sevseg = SevenSegment::with_alphabet(patterns, Alphabet::hexadecimal()).unwrap();
+    assert_eq!(sevseg.decode(encoded), Ok("BEEF".to_string()));
+
+    assert!(gen_a.generate(0x10000).is_none());
+}
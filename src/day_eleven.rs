@@ -2,10 +2,15 @@
 //! Day Eleven - Dumbo Octopus
 #![allow(dead_code)]
 
-use std::time::Instant;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{fs, thread};
 
 use ndarray::prelude::*;
 
+use crate::automaton::{Automaton, EdgePolicy, Neighbourhood, Rule};
 use crate::read_input;
 
 pub(crate) fn day_eleven_main() {
@@ -21,6 +26,31 @@ pub(crate) fn day_eleven_main() {
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
+/// Animate the octopuses in the terminal, `animate [steps] [fps] [frame dir]`
+pub(crate) fn animate_main(args: &[String]) {
+    let steps = args.first().map_or(Ok(100), |x| x.parse::<u64>());
+    let frame_rate = args.get(1).map_or(Ok(10.0), |x| x.parse::<f64>());
+    let (steps, frame_rate) = match (steps, frame_rate) {
+        (Ok(steps), Ok(frame_rate)) if frame_rate >= 0.0 => (steps, frame_rate),
+        _ => {
+            println!("Usage: animate [steps] [fps] [frame dir]");
+            return;
+        }
+    };
+    let opts = AnimationOptions {
+        steps,
+        frame_rate,
+        frame_dir: args.get(2).cloned(),
+        scale: 8,
+    };
+
+    let input = read_input::read_file("day_eleven_input.txt");
+    let mut octomap = OctoMap::new(input);
+    if let Err(err) = animate(&mut octomap, &opts, &mut io::stdout()) {
+        println!("Animation failed: {err}");
+    }
+}
+
 fn part_one(mut om: OctoMap) {
     for _ in 1..=100 {
         om.step();
@@ -28,20 +58,107 @@ fn part_one(mut om: OctoMap) {
     println!("Part One, Flashes: {}", om.flash_count);
 }
 
-fn part_two(mut om: OctoMap) {
-    let mut count = 0_u16;
-    while om.energy_map.sum() != 0 {
-        count += 1;
-        om.step();
+fn part_two(om: OctoMap) {
+    let report = om.find_cycle(10_000);
+    let first_sync = match &report {
+        Ok(report) => report.first_sync,
+        Err(no_cycle) => no_cycle.first_sync,
+    };
+    if let Some(sync) = first_sync {
+        println!("Part Two, Sync Step: {}", sync);
+    }
+
+    match report {
+        Ok(report) => println!(
+            "Part Two, Cycle of {} from Step {}, Flashes After 10^12 Steps: {}",
+            report.length,
+            report.start,
+            report.flashes_after(1_000_000_000_000)
+        ),
+        Err(_) => println!("Part Two, No Cycle Found"),
     }
-    println!("Part Two, Sync Step: {}", count);
+}
+
+/// Where the simulation falls into a loop, found by remembering every state
+#[derive(Debug, PartialEq)]
+struct CycleReport {
+    /// Steps taken to reach the first state that later repeats
+    start: u64,
+    /// Steps between repeats of that state
+    length: u64,
+    /// First step on which every octopus flashed
+    first_sync: Option<u64>,
+    /// Flashes of every step up to the first repeat, step one first
+    flashes: Vec<u64>,
+}
+
+/// What a cycle search that ran out of steps still learned
+#[derive(Debug, PartialEq)]
+struct NoCycle {
+    first_sync: Option<u64>,
+}
+
+impl CycleReport {
+    /// Total flashes over the first `steps` steps, however many there are
+    fn flashes_after(&self, steps: u64) -> u64 {
+        let sum =
+            |from: u64, to: u64| -> u64 { self.flashes[from as usize..to as usize].iter().sum() };
+
+        if steps <= self.start {
+            return sum(0, steps);
+        }
+        let laps = (steps - self.start) / self.length;
+        let rest = (steps - self.start) % self.length;
+        sum(0, self.start)
+            + laps * sum(self.start, self.start + self.length)
+            + sum(self.start, self.start + rest)
+    }
+}
+
+/// Octopuses gain a unit of energy each step and from each flashing
+/// neighbour, flash past nine and drop back to zero once they have flashed
+#[derive(Clone, Copy, Debug)]
+struct Octopus;
+
+impl Rule for Octopus {
+    type Cell = u16;
+
+    fn increment(&self, energy: &mut u16) {
+        *energy += 1;
+    }
+
+    fn threshold(&self, energy: &u16) -> bool {
+        *energy > 9
+    }
+
+    fn propagate(&self, energy: &mut u16) {
+        *energy += 1;
+    }
+
+    fn reset(&self, energy: &mut u16, flashed: bool) {
+        if flashed {
+            *energy = 0;
+        }
+    }
+}
+
+/// Flashes of a single step
+#[derive(Clone, Debug, PartialEq)]
+struct StepStats {
+    /// Steps taken so far, this one included
+    step: u64,
+    flashes: u64,
+    /// Flashes over every step so far, this one included
+    cumulative: u64,
+    flash_map: Array2<bool>,
 }
 
 #[derive(Clone, Debug)]
 struct OctoMap {
     energy_map: Array2<u16>,
-    flash_map: Array2<u16>,
-    flash_count: u16,
+    automaton: Automaton<Octopus>,
+    step_count: u64,
+    flash_count: u64,
 }
 
 impl OctoMap {
@@ -60,118 +177,137 @@ impl OctoMap {
             values_arr.move_into(energy_map.slice_mut(s![i, ..]));
         }
 
-        // Create flash map and count
-        let flash_map: Array2<u16> = Array2::zeros((m, n));
-        let flash_count = 0_u16;
+        let automaton = Automaton::new(Octopus, Neighbourhood::Moore, EdgePolicy::Bounded);
 
         OctoMap {
             energy_map,
-            flash_map,
-            flash_count,
+            automaton,
+            step_count: 0,
+            flash_count: 0,
         }
     }
 
-    fn step(&mut self) {
-        self.energy_map = &self.energy_map + 1;
+    /// Step a copy of the map until a state repeats, giving up after
+    /// `max_steps`
+    fn find_cycle(&self, max_steps: u64) -> Result<CycleReport, NoCycle> {
+        let mut energy_map = self.energy_map.clone();
+        let mut seen: HashMap<Array2<u16>, u64> = HashMap::new();
+        let mut flashes = Vec::new();
+        let mut first_sync = None;
 
-        let m = self.energy_map.nrows() - 1;
-        let n = self.energy_map.ncols() - 1;
-        let mut is_processing = true;
-        while is_processing {
-            is_processing = false;
+        for step in 0..=max_steps {
+            if let Some(&start) = seen.get(&energy_map) {
+                flashes.truncate(step as usize);
+                return Ok(CycleReport {
+                    start,
+                    length: step - start,
+                    first_sync,
+                    flashes,
+                });
+            }
+            seen.insert(energy_map.clone(), step);
 
-            let mut flashers = Vec::new();
-            for ((y, x), val) in self.energy_map.indexed_iter_mut() {
-                if *val > 9 && self.flash_map[[y, x]] == 0 {
-                    is_processing = true;
-                    self.flash_map[[y, x]] = 1;
-                    flashers.push((y, x));
-                }
+            let flash_map = self.automaton.step(&mut energy_map);
+            let count = flash_map.iter().filter(|&&f| f).count();
+            if first_sync.is_none() && count == flash_map.len() {
+                first_sync = Some(step + 1);
             }
+            flashes.push(count as u64);
+        }
+        Err(NoCycle { first_sync })
+    }
+
+    /// Advance one step, giving its flashes and which octopuses made them
+    fn step(&mut self) -> StepStats {
+        let flash_map = self.automaton.step(&mut self.energy_map);
+        let flashes = flash_map.iter().filter(|&&f| f).count() as u64;
+        self.step_count += 1;
+        self.flash_count += flashes;
+
+        StepStats {
+            step: self.step_count,
+            flashes,
+            cumulative: self.flash_count,
+            flash_map,
+        }
+    }
 
-            for (y, x) in flashers {
-                match (y == 0, y == m, x == 0, x == n) {
-                    (true, false, true, false) => {
-                        // Top Row, Left Column
-                        self.energy_map[[y, x + 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                        self.energy_map[[y + 1, x + 1]] += 1;
-                    }
-                    (true, false, false, false) => {
-                        // Top Row, Intermediate Columns
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y, x + 1]] += 1;
-                        self.energy_map[[y + 1, x - 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                        self.energy_map[[y + 1, x + 1]] += 1;
-                    }
-                    (true, false, false, true) => {
-                        // Top Row, Right Column
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y + 1, x - 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                    }
-                    (false, false, true, false) => {
-                        // Intermediate Rows, Left Column
-                        self.energy_map[[y - 1, x]] += 1;
-                        self.energy_map[[y - 1, x + 1]] += 1;
-                        self.energy_map[[y, x + 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                        self.energy_map[[y + 1, x + 1]] += 1;
-                    }
-                    (false, false, false, false) => {
-                        // Intermediate Rows, Intermediate Columns
-                        self.energy_map[[y - 1, x - 1]] += 1;
-                        self.energy_map[[y - 1, x]] += 1;
-                        self.energy_map[[y - 1, x + 1]] += 1;
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y, x + 1]] += 1;
-                        self.energy_map[[y + 1, x - 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                        self.energy_map[[y + 1, x + 1]] += 1;
-                    }
-                    (false, false, false, true) => {
-                        // Intermediate Rows, Right Column
-                        self.energy_map[[y - 1, x - 1]] += 1;
-                        self.energy_map[[y - 1, x]] += 1;
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y + 1, x - 1]] += 1;
-                        self.energy_map[[y + 1, x]] += 1;
-                    }
-                    (false, true, true, false) => {
-                        // Bottom Row, Left Column
-                        self.energy_map[[y - 1, x]] += 1;
-                        self.energy_map[[y - 1, x + 1]] += 1;
-                        self.energy_map[[y, x + 1]] += 1;
-                    }
-                    (false, true, false, false) => {
-                        // Bottom Row, Intermediate Columns
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y, x + 1]] += 1;
-                        self.energy_map[[y - 1, x - 1]] += 1;
-                        self.energy_map[[y - 1, x]] += 1;
-                        self.energy_map[[y - 1, x + 1]] += 1;
-                    }
-                    (false, true, false, true) => {
-                        // Bottom Row, Right Column
-                        self.energy_map[[y, x - 1]] += 1;
-                        self.energy_map[[y - 1, x - 1]] += 1;
-                        self.energy_map[[y - 1, x]] += 1;
-                    }
-                    _ => unreachable!(),
+    /// Energy levels as text, octopuses that just flashed in bold white and
+    /// the rest dimmed
+    fn render_frame(&self, flash_map: &Array2<bool>) -> String {
+        let mut out = String::new();
+        for (energy_row, flash_row) in self.energy_map.rows().into_iter().zip(flash_map.rows()) {
+            for (energy, &flashed) in energy_row.iter().zip(flash_row) {
+                if flashed {
+                    out.push_str(&format!("\x1b[1;97m{energy}\x1b[0m"));
+                } else {
+                    out.push_str(&format!("\x1b[2m{energy}\x1b[0m"));
                 }
             }
+            out.push('\n');
         }
+        out
+    }
 
-        for val in self.energy_map.iter_mut() {
-            if *val > 9 {
-                *val = 0;
+    /// Binary PGM image of the energy levels, flashes at full white, each
+    /// octopus `scale` pixels wide
+    fn to_pgm(&self, flash_map: &Array2<bool>, scale: usize) -> Vec<u8> {
+        let (m, n) = self.energy_map.dim();
+        let mut out = format!("P5\n{} {}\n255\n", n * scale, m * scale).into_bytes();
+        for (energy_row, flash_row) in self.energy_map.rows().into_iter().zip(flash_map.rows()) {
+            for _ in 0..scale {
+                for (&energy, &flashed) in energy_row.iter().zip(flash_row) {
+                    let grey = if flashed {
+                        255
+                    } else {
+                        (energy.min(9) * 20) as u8
+                    };
+                    out.extend(std::iter::repeat_n(grey, scale));
+                }
             }
         }
+        out
+    }
+}
 
-        self.flash_count += self.flash_map.sum() as u16;
-        self.flash_map.fill(0);
+/// Settings for watching the octopuses step by step
+#[derive(Debug)]
+struct AnimationOptions {
+    steps: u64,
+    /// Frames per second, zero for no delay between frames
+    frame_rate: f64,
+    /// Directory to write numbered PGM frames into, if any
+    frame_dir: Option<String>,
+    scale: usize,
+}
+
+/// Step the map, drawing each frame over the last on `out` and optionally
+/// saving it as `frame_0001.pgm` and so on
+fn animate<W: Write>(om: &mut OctoMap, opts: &AnimationOptions, out: &mut W) -> io::Result<()> {
+    if let Some(dir) = &opts.frame_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    for step in 1..=opts.steps {
+        let stats = om.step();
+        write!(out, "\x1b[H\x1b[2J")?;
+        write!(out, "{}", om.render_frame(&stats.flash_map))?;
+        writeln!(
+            out,
+            "Step {}, Flashes: {} of {}",
+            stats.step, stats.flashes, stats.cumulative
+        )?;
+        out.flush()?;
+
+        if let Some(dir) = &opts.frame_dir {
+            let path = Path::new(dir).join(format!("frame_{step:04}.pgm"));
+            fs::write(path, om.to_pgm(&stats.flash_map, opts.scale))?;
+        }
+        if opts.frame_rate > 0.0 {
+            thread::sleep(Duration::from_secs_f64(1.0 / opts.frame_rate));
+        }
     }
+    Ok(())
 }
 
 #[test]
@@ -221,3 +357,133 @@ fn test_dayeleven_part_two_actual() {
     }
     assert_eq!(count, 314);
 }
+
+#[test]
+fn test_dayeleven_find_cycle() {
+    let input = read_input::read_file("day_eleven_test_input.txt");
+    let octomap = OctoMap::new(input);
+    let report = octomap.find_cycle(1000).unwrap();
+
+    // Once in sync the octopuses flash together every ten steps
+    assert_eq!(report.first_sync, Some(195));
+    assert_eq!(report.start, 195);
+    assert_eq!(report.length, 10);
+    assert_eq!(report.flashes_after(10), 204);
+    assert_eq!(report.flashes_after(100), 1656);
+
+    let mut stepped = octomap.clone();
+    for _ in 0..500 {
+        stepped.step();
+    }
+    assert_eq!(report.flashes_after(500), stepped.flash_count);
+
+    let before_sync = report.flashes_after(194);
+    assert_eq!(report.flashes_after(500), before_sync + 31 * 100);
+    assert_eq!(
+        report.flashes_after(1_000_000_000_000),
+        before_sync + ((1_000_000_000_000 - 195) / 10 + 1) * 100
+    );
+
+    assert_eq!(octomap.find_cycle(100), Err(NoCycle { first_sync: None }));
+    // Synced, but not yet back to a state seen before
+    assert_eq!(
+        octomap.find_cycle(200),
+        Err(NoCycle {
+            first_sync: Some(195)
+        })
+    );
+}
+
+#[test]
+fn test_dayeleven_find_cycle_actual() {
+    let input = read_input::read_file("day_eleven_input.txt");
+    let report = OctoMap::new(input).find_cycle(1000).unwrap();
+
+    assert_eq!(report.first_sync, Some(314));
+    assert_eq!(report.flashes_after(100), 1591);
+}
+
+#[test]
+fn test_dayeleven_render_frame() {
+    let input = read_input::read_file("day_eleven_test_input.txt");
+    let mut octomap = OctoMap::new(input);
+    for _ in 0..194 {
+        octomap.step();
+    }
+
+    // Step 195 is the first in sync, everything flashes at once
+    let flash_map = octomap.step().flash_map;
+    let frame = octomap.render_frame(&flash_map);
+    assert_eq!(frame.lines().count(), 10);
+    assert_eq!(frame.matches("\x1b[1;97m0").count(), 100);
+
+    let flash_map = octomap.step().flash_map;
+    let frame = octomap.render_frame(&flash_map);
+    assert_eq!(frame.matches("\x1b[2m1").count(), 100);
+
+    let flash_map = octomap.step().flash_map;
+    let pgm = octomap.to_pgm(&flash_map, 3);
+    let header = b"P5\n30 30\n255\n";
+    assert_eq!(&pgm[..header.len()], header);
+    assert_eq!(pgm.len(), header.len() + 30 * 30);
+    assert!(pgm[header.len()..].iter().all(|&g| g == 40));
+}
+
+#[test]
+fn test_dayeleven_animate() {
+    let input = read_input::read_file("day_eleven_test_input.txt");
+    let mut octomap = OctoMap::new(input);
+    // Unique to this run, so concurrent or leftover runs can't clash
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!(
+        "aoc_2021_day_eleven_frames_{}_{}",
+        std::process::id(),
+        nanos
+    ));
+    let opts = AnimationOptions {
+        steps: 3,
+        frame_rate: 0.0,
+        frame_dir: Some(dir.to_string_lossy().to_string()),
+        scale: 1,
+    };
+
+    let mut out = Vec::new();
+    animate(&mut octomap, &opts, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert_eq!(text.matches("\x1b[H\x1b[2J").count(), 3);
+    assert!(text.ends_with("Step 3, Flashes: 45 of 80\n"));
+
+    for step in 1..=3 {
+        let frame = fs::read(dir.join(format!("frame_{step:04}.pgm"))).unwrap();
+        assert!(frame.starts_with(b"P5\n10 10\n255\n"));
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_dayeleven_step_stats() {
+    let input = read_input::read_file("day_eleven_test_input.txt");
+    let mut octomap = OctoMap::new(input);
+
+    let history: Vec<StepStats> = (0..10).map(|_| octomap.step()).collect();
+    let flashes: Vec<u64> = history.iter().map(|x| x.flashes).collect();
+    assert_eq!(flashes, vec![0, 35, 45, 16, 8, 1, 7, 24, 39, 29]);
+    assert_eq!(history[9].step, 10);
+    assert_eq!(history[9].cumulative, 204);
+    assert_eq!(history[1].flash_map.iter().filter(|&&f| f).count(), 35);
+    assert!(!history[1].flash_map[[0, 0]]);
+    assert!(history[1].flash_map[[0, 2]]);
+
+    // Well past where a u16 total would have wrapped
+    let report = octomap.find_cycle(1000).unwrap();
+    for _ in 10..7000 {
+        octomap.step();
+    }
+    let stats = octomap.step();
+    assert_eq!(stats.step, 7001);
+    assert!(stats.cumulative > u16::MAX as u64);
+    assert_eq!(stats.cumulative, 204 + report.flashes_after(6991));
+}
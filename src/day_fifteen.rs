@@ -103,10 +103,7 @@ fn create_map(input: Vec<String>) -> Array2<u32> {
     let n = input[0].len();
     let mut chiton_map: Array2<u32> = Array2::zeros((m, n));
     for (i, line) in input.iter().enumerate() {
-        let values_vec: Vec<u32> = line
-            .chars()
-            .map(|x| x.to_digit(10).unwrap() as u32)
-            .collect();
+        let values_vec: Vec<u32> = line.chars().map(|x| x.to_digit(10).unwrap()).collect();
         let values_arr = Array::from_vec(values_vec);
         values_arr.move_into(chiton_map.slice_mut(s![i, ..]));
     }
@@ -50,14 +50,8 @@ struct PointPair {
 impl PointPair {
     fn new(s: &str, sep: &str) -> Option<PointPair> {
         let vals: Vec<&str> = s.split(sep).collect();
-        let a = match Point::<u32>::new(vals[0], ',') {
-            Some(p) => p,
-            None => return None,
-        };
-        let b = match Point::<u32>::new(vals[1], ',') {
-            Some(p) => p,
-            None => return None,
-        };
+        let a = Point::<u32>::new(vals[0], ',')?;
+        let b = Point::<u32>::new(vals[1], ',')?;
 
         Some(PointPair { a, b })
     }
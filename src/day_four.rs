@@ -32,7 +32,7 @@ fn get_boards(input: &mut Vec<String>) -> Vec<BingoBoard> {
 
         if !row.is_empty() {
             for val in row_vals {
-                if val.eq("") {
+                if val.is_empty() {
                     continue;
                 }
                 board_builder.push(val.parse::<u8>().unwrap())
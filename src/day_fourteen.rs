@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::time::Instant;
 
 use itertools::Itertools;
+use ndarray::prelude::*;
 
 use crate::read_input;
 
@@ -14,18 +15,18 @@ pub(crate) fn day_fourteen_main() {
     let now = Instant::now();
 
     let mut input = read_input::read_file("day_fourteen_input.txt");
-    let poly_counts = initialize_poly_counts(input.remove(0));
+    let polymer = initialize_polymer(input.remove(0));
     let poly_rules: HashMap<String, [String; 2]> = initialize_poly_rules(input);
 
-    let part_one_counts = polymerize(10, &poly_counts, &poly_rules);
-    let (one_min, one_max) = calc_min_max(part_one_counts).unwrap();
+    let part_one_counts = polymerize(10, &polymer, &poly_rules).unwrap();
+    let (one_min, one_max) = calc_min_max(&part_one_counts).unwrap();
     println!(
         "Part One, Max: {one_max}, Min: {one_min}, Diff: {}",
         one_max - one_min
     );
 
-    let part_two_counts = polymerize(40, &poly_counts, &poly_rules);
-    let (two_min, two_max) = calc_min_max(part_two_counts).unwrap();
+    let part_two_counts = polymerize(40, &polymer, &poly_rules).unwrap();
+    let (two_min, two_max) = calc_min_max(&part_two_counts).unwrap();
     println!(
         "Part Two, Max: {two_max}, Min: {two_min}, Diff: {}",
         two_max - two_min
@@ -34,32 +35,44 @@ pub(crate) fn day_fourteen_main() {
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
-fn calc_min_max(poly_counts: HashMap<String, u64>) -> Option<(u64, u64)> {
-    let mut counts_map: HashMap<char, u64> = HashMap::new();
-    for (key, val) in poly_counts.iter() {
-        *counts_map.entry(key.chars().next().unwrap()).or_insert(0) += val;
-        *counts_map.entry(key.chars().last().unwrap()).or_insert(0) += val;
-    }
+/// Pair counts of a polymer along with exact counts of its elements, which the
+/// overlapping pairs alone only give up to the template's end elements
+#[derive(Clone, Debug, PartialEq)]
+struct Polymer {
+    pairs: HashMap<String, u64>,
+    elements: HashMap<char, u64>,
+}
 
-    let minmax = counts_map.iter().minmax_by_key(|entry| entry.1);
-    if let Some((min, max)) = minmax.into_option() {
-        let min_count = (*min.1 + 1) / 2;
-        let max_count = (*max.1 + 1) / 2;
-        Some((min_count, max_count))
-    } else {
-        None
+impl Polymer {
+    /// Every element with its count, in element order
+    fn histogram(&self) -> Vec<(char, u64)> {
+        self.elements
+            .iter()
+            .map(|(&element, &count)| (element, count))
+            .sorted()
+            .collect()
     }
 }
 
-fn initialize_poly_counts(poly_string: String) -> HashMap<String, u64> {
-    poly_string
+fn calc_min_max(polymer: &Polymer) -> Option<(u64, u64)> {
+    polymer.elements.values().copied().minmax().into_option()
+}
+
+fn initialize_polymer(poly_string: String) -> Polymer {
+    let pairs = poly_string
         .char_indices()
         .skip(1)
         .fold(HashMap::new(), |mut map, (i, _)| {
             *map.entry(poly_string.get(i - 1..=i).unwrap().to_string())
                 .or_insert(0) += 1;
             map
-        })
+        });
+    let elements = poly_string.chars().fold(HashMap::new(), |mut map, c| {
+        *map.entry(c).or_insert(0) += 1;
+        map
+    });
+
+    Polymer { pairs, elements }
 }
 
 fn initialize_poly_rules(raw_rules: Vec<String>) -> HashMap<String, [String; 2]> {
@@ -90,25 +103,220 @@ fn parse_rule(line_map: &str) -> Option<(String, [String; 2])> {
     }
 }
 
-fn polymerize(
-    n: u8,
-    poly_counts: &HashMap<String, u64>,
-    poly_rules: &HashMap<String, [String; 2]>,
-) -> HashMap<String, u64> {
-    if n == 0 {
-        return poly_counts.clone();
+/// How counts are kept while polymerizing
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Counting {
+    /// Exact counts, failing once they no longer fit
+    Exact,
+    /// Counts modulo a value, which never overflow
+    Modulo(u64),
+}
+
+#[derive(Debug, PartialEq)]
+enum PolymerError {
+    Overflow,
+    ZeroModulus,
+}
+
+impl Counting {
+    fn reduce(self, a: u128) -> u128 {
+        match self {
+            Counting::Exact => a,
+            Counting::Modulo(m) => a % m as u128,
+        }
     }
 
-    let iter_counts: HashMap<String, u64> =
-        poly_counts.iter().fold(HashMap::new(), |mut map, (k, v)| {
-            *map.entry(poly_rules.get(k).unwrap()[0].clone())
-                .or_insert(0) += v;
-            *map.entry(poly_rules.get(k).unwrap()[1].clone())
-                .or_insert(0) += v;
-            map
-        });
+    /// Both operands are already reduced, so below the modulus
+    fn add(self, a: u128, b: u128) -> Result<u128, PolymerError> {
+        match self {
+            Counting::Exact => a.checked_add(b).ok_or(PolymerError::Overflow),
+            Counting::Modulo(_) => Ok(self.reduce(a + b)),
+        }
+    }
+
+    fn mul(self, a: u128, b: u128) -> Result<u128, PolymerError> {
+        match self {
+            Counting::Exact => a.checked_mul(b).ok_or(PolymerError::Overflow),
+            Counting::Modulo(_) => Ok(self.reduce(a * b)),
+        }
+    }
+}
 
-    polymerize(n - 1, &iter_counts, poly_rules)
+/// Polymerization as a linear map. Elements are interned by sorted index and
+/// the pair `(a, b)` by `a * n + b`, so a polymer is a vector of every pair
+/// count followed by every element count, and one step multiplies it by the
+/// transition matrix.
+#[derive(Debug)]
+struct PolymerSystem {
+    elements: Vec<char>,
+    transition: Array2<u128>,
+}
+
+impl PolymerSystem {
+    fn new(polymer: &Polymer, poly_rules: &HashMap<String, [String; 2]>) -> PolymerSystem {
+        let elements: Vec<char> = polymer
+            .elements
+            .keys()
+            .copied()
+            .chain(
+                poly_rules
+                    .values()
+                    .flat_map(|[a, b]| a.chars().chain(b.chars())),
+            )
+            .sorted()
+            .dedup()
+            .collect();
+        let n = elements.len();
+        let id = |c: char| elements.binary_search(&c).unwrap();
+
+        let mut transition = Array2::zeros((n * n + n, n * n + n));
+        for (a, b) in (0..n).cartesian_product(0..n) {
+            let pair = a * n + b;
+            let key = String::from_iter([elements[a], elements[b]]);
+            match poly_rules.get(&key) {
+                Some([left, _]) => {
+                    let c = id(left.chars().last().unwrap());
+                    transition[[a * n + c, pair]] += 1;
+                    transition[[c * n + b, pair]] += 1;
+                    transition[[n * n + c, pair]] += 1;
+                }
+                None => transition[[pair, pair]] = 1,
+            }
+        }
+        for e in 0..n {
+            transition[[n * n + e, n * n + e]] = 1;
+        }
+
+        PolymerSystem {
+            elements,
+            transition,
+        }
+    }
+
+    fn pair_index(&self, pair: &str) -> Option<usize> {
+        let mut chars = pair.chars();
+        let a = self.elements.binary_search(&chars.next()?).ok()?;
+        let b = self.elements.binary_search(&chars.next()?).ok()?;
+        Some(a * self.elements.len() + b)
+    }
+
+    fn state(&self, polymer: &Polymer) -> Array1<u128> {
+        let n = self.elements.len();
+        let mut state = Array1::zeros(n * n + n);
+        for (pair, &count) in &polymer.pairs {
+            state[self.pair_index(pair).unwrap()] += count as u128;
+        }
+        for (element, &count) in &polymer.elements {
+            state[n * n + self.elements.binary_search(element).unwrap()] += count as u128;
+        }
+        state
+    }
+
+    /// Counts of every pair then every element after `steps`, squaring the
+    /// transition matrix once per bit of `steps`
+    fn counts(
+        &self,
+        polymer: &Polymer,
+        steps: u64,
+        counting: Counting,
+    ) -> Result<Array1<u128>, PolymerError> {
+        if counting == Counting::Modulo(0) {
+            return Err(PolymerError::ZeroModulus);
+        }
+
+        let mut state = self.state(polymer).mapv(|x| counting.reduce(x));
+        let mut power = self.transition.mapv(|x| counting.reduce(x));
+        let mut steps = steps;
+        while steps > 0 {
+            if steps & 1 == 1 {
+                state = mat_vec(&power, &state, counting)?;
+            }
+            steps >>= 1;
+            if steps > 0 {
+                power = mat_mul(&power, &power, counting)?;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Every element with its count after `steps`, in element order
+    fn histogram(
+        &self,
+        polymer: &Polymer,
+        steps: u64,
+        counting: Counting,
+    ) -> Result<Vec<(char, u128)>, PolymerError> {
+        let n = self.elements.len();
+        let counts = self.counts(polymer, steps, counting)?;
+        Ok(self
+            .elements
+            .iter()
+            .copied()
+            .zip(counts.iter().skip(n * n).copied())
+            .collect())
+    }
+}
+
+fn mat_mul(
+    a: &Array2<u128>,
+    b: &Array2<u128>,
+    counting: Counting,
+) -> Result<Array2<u128>, PolymerError> {
+    let mut c = Array2::zeros((a.nrows(), b.ncols()));
+    for ((i, k), &a_ik) in a.indexed_iter() {
+        // The transition matrix is mostly zeros
+        if a_ik == 0 {
+            continue;
+        }
+        for (j, &b_kj) in b.row(k).indexed_iter() {
+            c[[i, j]] = counting.add(c[[i, j]], counting.mul(a_ik, b_kj)?)?;
+        }
+    }
+    Ok(c)
+}
+
+fn mat_vec(
+    a: &Array2<u128>,
+    v: &Array1<u128>,
+    counting: Counting,
+) -> Result<Array1<u128>, PolymerError> {
+    let mut out = Array1::zeros(a.nrows());
+    for ((i, k), &a_ik) in a.indexed_iter() {
+        if a_ik != 0 {
+            out[i] = counting.add(out[i], counting.mul(a_ik, v[k])?)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Apply the insertion rules `n` times with exact counts. Each pair with a
+/// rule inserts one of its middle element per occurrence, pairs without a rule
+/// are left alone.
+fn polymerize(
+    n: u64,
+    polymer: &Polymer,
+    poly_rules: &HashMap<String, [String; 2]>,
+) -> Result<Polymer, PolymerError> {
+    let system = PolymerSystem::new(polymer, poly_rules);
+    let counts = system.counts(polymer, n, Counting::Exact)?;
+    let to_u64 = |x: u128| u64::try_from(x).map_err(|_| PolymerError::Overflow);
+
+    let size = system.elements.len();
+    let mut next = Polymer {
+        pairs: HashMap::new(),
+        elements: HashMap::new(),
+    };
+    for (index, &count) in counts.iter().enumerate().filter(|(_, &x)| x > 0) {
+        if index < size * size {
+            let pair =
+                String::from_iter([system.elements[index / size], system.elements[index % size]]);
+            next.pairs.insert(pair, to_u64(count)?);
+        } else {
+            next.elements
+                .insert(system.elements[index - size * size], to_u64(count)?);
+        }
+    }
+    Ok(next)
 }
 
 #[test]
@@ -216,16 +424,16 @@ fn test_dayfourteen_parserule_some() {
 #[test]
 fn test_dayfourteen_polymerize_example() {
     let mut input = read_input::read_file("day_fourteen_test_input.txt");
-    let poly_counts = initialize_poly_counts(input.remove(0));
+    let polymer = initialize_polymer(input.remove(0));
     let poly_rules: HashMap<String, [String; 2]> = initialize_poly_rules(input);
 
-    let part_one_counts = polymerize(10, &poly_counts, &poly_rules);
-    let (one_min, one_max) = calc_min_max(part_one_counts).unwrap();
+    let part_one_counts = polymerize(10, &polymer, &poly_rules).unwrap();
+    let (one_min, one_max) = calc_min_max(&part_one_counts).unwrap();
     let part_one_diff = one_max - one_min;
     assert_eq!(part_one_diff, 1588, "testing example, part one difference");
 
-    let part_two_counts = polymerize(40, &poly_counts, &poly_rules);
-    let (two_min, two_max) = calc_min_max(part_two_counts).unwrap();
+    let part_two_counts = polymerize(40, &polymer, &poly_rules).unwrap();
+    let (two_min, two_max) = calc_min_max(&part_two_counts).unwrap();
     let part_two_diff = two_max - two_min;
     assert_eq!(
         part_two_diff, 2188189693529,
@@ -236,19 +444,100 @@ fn test_dayfourteen_polymerize_example() {
 #[test]
 fn test_dayfourteen_polymerize_actual() {
     let mut input = read_input::read_file("day_fourteen_input.txt");
-    let poly_counts = initialize_poly_counts(input.remove(0));
+    let polymer = initialize_polymer(input.remove(0));
     let poly_rules: HashMap<String, [String; 2]> = initialize_poly_rules(input);
 
-    let part_one_counts = polymerize(10, &poly_counts, &poly_rules);
-    let (one_min, one_max) = calc_min_max(part_one_counts).unwrap();
+    let part_one_counts = polymerize(10, &polymer, &poly_rules).unwrap();
+    let (one_min, one_max) = calc_min_max(&part_one_counts).unwrap();
     let part_one_diff = one_max - one_min;
     assert_eq!(part_one_diff, 2375, "testing actual, part one difference");
 
-    let part_two_counts = polymerize(40, &poly_counts, &poly_rules);
-    let (two_min, two_max) = calc_min_max(part_two_counts).unwrap();
+    let part_two_counts = polymerize(40, &polymer, &poly_rules).unwrap();
+    let (two_min, two_max) = calc_min_max(&part_two_counts).unwrap();
     let part_two_diff = two_max - two_min;
     assert_eq!(
         part_two_diff, 1976896901756,
         "testing actual, part two difference"
     );
 }
+
+#[test]
+fn test_dayfourteen_element_histogram() {
+    let mut input = read_input::read_file("day_fourteen_test_input.txt");
+    let polymer = initialize_polymer(input.remove(0));
+    let poly_rules: HashMap<String, [String; 2]> = initialize_poly_rules(input);
+
+    assert_eq!(polymer.histogram(), vec![('B', 1), ('C', 1), ('N', 2)]);
+
+    // NNCB becomes NCNBCHB
+    let step_one = polymerize(1, &polymer, &poly_rules).unwrap();
+    assert_eq!(
+        step_one.histogram(),
+        vec![('B', 2), ('C', 2), ('H', 1), ('N', 2)]
+    );
+
+    let step_ten = polymerize(10, &polymer, &poly_rules).unwrap();
+    assert_eq!(
+        step_ten.histogram(),
+        vec![('B', 1749), ('C', 298), ('H', 161), ('N', 865)]
+    );
+    assert_eq!(step_ten.elements.values().sum::<u64>(), 3073);
+}
+
+#[test]
+fn test_dayfourteen_element_edges() {
+    let poly_rules: HashMap<String, [String; 2]> =
+        initialize_poly_rules(vec!["".to_string(), "AB -> A".to_string()]);
+
+    // A lone element has no pairs to count it by
+    let single = polymerize(5, &initialize_polymer("A".to_string()), &poly_rules).unwrap();
+    assert_eq!(single.histogram(), vec![('A', 1)]);
+
+    // Only AB has a rule, so each step adds one A between the last A and B
+    let polymer = polymerize(3, &initialize_polymer("BAB".to_string()), &poly_rules).unwrap();
+    assert_eq!(polymer.histogram(), vec![('A', 4), ('B', 2)]);
+    assert_eq!(calc_min_max(&polymer), Some((2, 4)));
+}
+
+#[test]
+fn test_dayfourteen_polymer_system() {
+    let mut input = read_input::read_file("day_fourteen_test_input.txt");
+    let polymer = initialize_polymer(input.remove(0));
+    let poly_rules: HashMap<String, [String; 2]> = initialize_poly_rules(input);
+    let system = PolymerSystem::new(&polymer, &poly_rules);
+
+    assert_eq!(system.elements, vec!['B', 'C', 'H', 'N']);
+    assert_eq!(system.pair_index("NC"), Some(13));
+    assert_eq!(system.pair_index("NX"), None);
+
+    let exact = system.histogram(&polymer, 40, Counting::Exact).unwrap();
+    let modular = system
+        .histogram(&polymer, 40, Counting::Modulo(1_000_000_007))
+        .unwrap();
+    for ((e, x), (f, y)) in exact.iter().zip(&modular) {
+        assert_eq!(e, f);
+        assert_eq!(x % 1_000_000_007, *y);
+    }
+    assert_eq!(exact[0], ('B', 2192039569602));
+    assert_eq!(exact[2], ('H', 3849876073));
+
+    // Every pair has a rule, so the length goes from 4 to 3 * 2^steps + 1
+    let huge = system
+        .histogram(&polymer, u64::MAX, Counting::Modulo(1_000_000_007))
+        .unwrap();
+    let total = huge.iter().map(|(_, x)| x).sum::<u128>() % 1_000_000_007;
+    assert_eq!(total, 944592291);
+
+    assert_eq!(
+        system.histogram(&polymer, 200, Counting::Exact),
+        Err(PolymerError::Overflow)
+    );
+    assert_eq!(
+        polymerize(70, &polymer, &poly_rules),
+        Err(PolymerError::Overflow)
+    );
+    assert_eq!(
+        system.counts(&polymer, 1, Counting::Modulo(0)),
+        Err(PolymerError::ZeroModulus)
+    );
+}
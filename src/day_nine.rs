@@ -2,10 +2,11 @@
 //! Day Nine - Smoke Basin
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::time::Instant;
+use std::{fs, io};
 
-use ndarray::{s, Array, Array2};
+use ndarray::{Array, Array2, s};
 
 use crate::read_input;
 
@@ -26,54 +27,130 @@ fn part_one(b: &Basin) {
     println!("Part One, Total Risk Level: {}", b.get_total_risk_level());
 }
 
-fn part_two(b: Basin) -> u32 {
-    let mut lp_sizes = Vec::new();
-    for lp in &b.low_points {
-        let mut lp_set = HashSet::new();
-        lp_set.insert(*lp);
-
-        let mut lp_res = expand_dir(&b, &lp_set);
-        while !lp_res.is_empty() {
-            lp_set = lp_set.union(&lp_res).copied().collect();
-            lp_res = expand_dir(&b, &lp_set);
-        }
-
-        lp_sizes.push(lp_set.len() as u32);
-    }
-
-    lp_sizes.sort_unstable_by(|a, b| b.cmp(a));
-    let prd: u32 = lp_sizes[..3].iter().product();
+fn part_two(b: Basin) -> u64 {
+    let prd = b.label_basins().top_product(3);
     println!("Part Two, Top Three Product: {}", prd);
     prd
 }
 
-fn expand_dir(basin: &Basin, lows: &HashSet<MapLocation>) -> HashSet<MapLocation> {
-    let mut new_lows: HashSet<MapLocation> = HashSet::new();
-
-    for dir in [
-        SearchDir::Up,
-        SearchDir::Down,
-        SearchDir::Left,
-        SearchDir::Right,
-    ] {
-        for lp in lows {
-            match basin.get_step(lp, &dir) {
-                Some(loc) if !lows.contains(&loc) && loc.v != 9 => {
-                    new_lows.insert(loc);
-                }
-                _ => (),
-            }
-        }
+/// Size and lowest location of a single basin
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BasinInfo {
+    size: usize,
+    low_point: MapLocation,
+}
+
+/// Basin label of every location, `None` along the ridges
+#[derive(Debug)]
+struct BasinLabels {
+    labels: Array2<Option<usize>>,
+    basins: Vec<BasinInfo>,
+}
+
+impl BasinLabels {
+    /// Product of the `k` largest basin sizes
+    fn top_product(&self, k: usize) -> u64 {
+        let mut sizes: Vec<u64> = self.basins.iter().map(|b| b.size as u64).collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        sizes.iter().take(k).product()
     }
-    new_lows
 }
 
+/// Evenly spread, saturated colour for a basin label
+fn basin_colour(label: usize) -> [u8; 3] {
+    // Golden ratio steps around the hue wheel keep neighbours distinct
+    let hue = (label as f64 * 0.618_033_988_75).fract() * 6.0;
+    let f = hue.fract();
+    let (r, g, b) = match hue as u8 {
+        0 => (1.0, f, 0.0),
+        1 => (1.0 - f, 1.0, 0.0),
+        2 => (0.0, 1.0, f),
+        3 => (0.0, 1.0 - f, 1.0),
+        4 => (f, 0.0, 1.0),
+        _ => (1.0, 0.0, 1.0 - f),
+    };
+    let scale = |c: f64| (55.0 + c * 200.0) as u8;
+    [scale(r), scale(g), scale(b)]
+}
+
+const RIDGE_COLOUR: [u8; 3] = [40, 40, 40];
+const LOW_POINT_COLOUR: [u8; 3] = [255, 255, 255];
+
 #[derive(Debug)]
 enum SearchDir {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl SearchDir {
+    /// Row and column offsets of a single step
+    fn offset(&self) -> (isize, isize) {
+        match self {
+            SearchDir::Up => (-1, 0),
+            SearchDir::Down => (1, 0),
+            SearchDir::Left => (0, -1),
+            SearchDir::Right => (0, 1),
+            SearchDir::UpLeft => (-1, -1),
+            SearchDir::UpRight => (-1, 1),
+            SearchDir::DownLeft => (1, -1),
+            SearchDir::DownRight => (1, 1),
+        }
+    }
+}
+
+/// Which locations count as adjacent when comparing heights and growing
+/// basins
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Neighbourhood {
+    /// Orthogonal steps only
+    Four,
+    /// Orthogonal and diagonal steps
+    Eight,
+}
+
+impl Neighbourhood {
+    fn dirs(&self) -> &'static [SearchDir] {
+        match self {
+            Neighbourhood::Four => &[
+                SearchDir::Up,
+                SearchDir::Down,
+                SearchDir::Left,
+                SearchDir::Right,
+            ],
+            Neighbourhood::Eight => &[
+                SearchDir::Up,
+                SearchDir::Down,
+                SearchDir::Left,
+                SearchDir::Right,
+                SearchDir::UpLeft,
+                SearchDir::UpRight,
+                SearchDir::DownLeft,
+                SearchDir::DownRight,
+            ],
+        }
+    }
+}
+
+/// Terrain rules, locations at or above `wall` are ridges separating basins
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BasinRules {
+    wall: u8,
+    neighbourhood: Neighbourhood,
+}
+
+impl Default for BasinRules {
+    fn default() -> BasinRules {
+        BasinRules {
+            wall: 9,
+            neighbourhood: Neighbourhood::Four,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -97,10 +174,15 @@ impl MapLocation {
 struct Basin {
     height_map: Array2<u8>,
     low_points: Vec<MapLocation>,
+    rules: BasinRules,
 }
 
 impl Basin {
     fn new(readings: Vec<String>) -> Basin {
+        Basin::with_rules(readings, BasinRules::default())
+    }
+
+    fn with_rules(readings: Vec<String>, rules: BasinRules) -> Basin {
         let m = readings.len();
         let n = readings[0].len();
 
@@ -115,82 +197,147 @@ impl Basin {
             values_arr.assign_to(height_map.slice_mut(s![i, ..]));
         }
 
-        // Find low points
-        let mut low_points: Vec<MapLocation> = Vec::new();
-        for ((y, x), val) in height_map.indexed_iter() {
-            use std::cmp::Ordering::*;
-
-            // Check horizontal
-            let is_low_h = {
-                match (x.cmp(&0), x.cmp(&(n - 1))) {
-                    (Equal, Less) => val <= &height_map[[y, x + 1]],
-                    (Greater, Less) => {
-                        val < &height_map[[y, x - 1]] && val < &height_map[[y, x + 1]]
-                    }
-                    (Greater, Equal) => val <= &height_map[[y, x - 1]],
-                    _ => panic!("Horizontal compare mismatch"),
-                }
+        let mut basin = Basin {
+            height_map,
+            low_points: Vec::new(),
+            rules,
+        };
+
+        // Find low points, lower than every neighbour
+        for ((y, x), &v) in basin.height_map.indexed_iter() {
+            let loc = MapLocation::new(y, x, v);
+            let is_low = rules
+                .neighbourhood
+                .dirs()
+                .iter()
+                .filter_map(|dir| basin.get_step(&loc, dir))
+                .all(|next| v < next.v);
+
+            if is_low {
+                basin.low_points.push(loc);
+            }
+        }
+        basin
+    }
+
+    fn get_total_risk_level(&self) -> u32 {
+        self.low_points
+            .iter()
+            .fold(0, |acc, &x| acc + x.v as u32 + 1)
+    }
+
+    /// Label connected basins in one pass, flooding each unlabeled location
+    /// below the ridge height with a breadth first search
+    fn label_basins(&self) -> BasinLabels {
+        let mut labels: Array2<Option<usize>> = Array2::from_elem(self.height_map.dim(), None);
+        let mut basins = Vec::new();
+        let mut frontier = VecDeque::new();
+
+        for ((y, x), &v) in self.height_map.indexed_iter() {
+            if v >= self.rules.wall || labels[[y, x]].is_some() {
+                continue;
+            }
+
+            let label = basins.len();
+            let start = MapLocation::new(y, x, v);
+            let mut info = BasinInfo {
+                size: 0,
+                low_point: start,
             };
+            labels[[y, x]] = Some(label);
+            frontier.push_back(start);
+
+            while let Some(loc) = frontier.pop_front() {
+                info.size += 1;
+                if loc.v < info.low_point.v {
+                    info.low_point = loc;
+                }
 
-            // Check vertical
-            let is_low_v = {
-                match (y.cmp(&0), y.cmp(&(m - 1))) {
-                    (Equal, Less) => val <= &height_map[[y + 1, x]],
-                    (Greater, Less) => {
-                        val < &height_map[[y - 1, x]] && val < &height_map[[y + 1, x]]
+                for dir in self.rules.neighbourhood.dirs() {
+                    match self.get_step(&loc, dir) {
+                        Some(next)
+                            if next.v < self.rules.wall && labels[[next.m, next.n]].is_none() =>
+                        {
+                            labels[[next.m, next.n]] = Some(label);
+                            frontier.push_back(next);
+                        }
+                        _ => (),
                     }
-                    (Greater, Equal) => val <= &height_map[[y - 1, x]],
-                    _ => panic!("Vertical compare mismatch"),
                 }
-            };
+            }
+            basins.push(info);
+        }
 
-            if is_low_h && is_low_v {
-                low_points.push(MapLocation::new(y, x, *val));
+        BasinLabels { labels, basins }
+    }
+
+    /// Colour of each location, basins darkening as they rise, ridges walled
+    /// off and low points picked out in white
+    fn colour_map(&self, labeled: &BasinLabels) -> Array2<[u8; 3]> {
+        let mut colours = Array2::from_elem(self.height_map.dim(), RIDGE_COLOUR);
+        for (((y, x), label), colour) in labeled.labels.indexed_iter().zip(colours.iter_mut()) {
+            if let Some(label) = label {
+                let shade = 1.0 - self.height_map[[y, x]] as f64 / 12.0;
+                *colour = basin_colour(*label).map(|c| (c as f64 * shade) as u8);
             }
         }
-        Basin {
-            height_map,
-            low_points,
+        for basin in &labeled.basins {
+            colours[[basin.low_point.m, basin.low_point.n]] = LOW_POINT_COLOUR;
         }
+        colours
     }
 
-    fn get_total_risk_level(&self) -> u32 {
-        self.low_points
-            .iter()
-            .fold(0, |acc, &x| acc + x.v as u32 + 1)
+    /// Render the height map with 24-bit ANSI background colours, ridges as
+    /// `#` and low points as `*`
+    fn render_ansi(&self, labeled: &BasinLabels) -> String {
+        let colours = self.colour_map(labeled);
+        let mut out = String::new();
+        for (y, row) in colours.rows().into_iter().enumerate() {
+            for (x, [r, g, b]) in row.iter().enumerate() {
+                let glyph = match labeled.labels[[y, x]] {
+                    None => '#',
+                    Some(label)
+                        if labeled.basins[label].low_point.m == y
+                            && labeled.basins[label].low_point.n == x =>
+                    {
+                        '*'
+                    }
+                    Some(_) => char::from_digit(self.height_map[[y, x]] as u32, 10).unwrap(),
+                };
+                out.push_str(&format!("\x1b[30;48;2;{r};{g};{b}m{glyph}"));
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
     }
 
-    fn get_step(&self, lp: &MapLocation, search_dir: &SearchDir) -> Option<MapLocation> {
-        let m = self.height_map.nrows() - 1;
-        let n = self.height_map.ncols() - 1;
-        match (lp.m, lp.n, &search_dir) {
-            (0, _, SearchDir::Up) => None,
-            (y, _, SearchDir::Down) if y == m => None,
-            (_, 0, SearchDir::Left) => None,
-            (_, x, SearchDir::Right) if x == n => None,
-            _ => match search_dir {
-                SearchDir::Up => Some(MapLocation {
-                    m: lp.m - 1,
-                    n: lp.n,
-                    v: self.height_map[[lp.m - 1, lp.n]],
-                }),
-                SearchDir::Down => Some(MapLocation {
-                    m: lp.m + 1,
-                    n: lp.n,
-                    v: self.height_map[[lp.m + 1, lp.n]],
-                }),
-                SearchDir::Left => Some(MapLocation {
-                    m: lp.m,
-                    n: lp.n - 1,
-                    v: self.height_map[[lp.m, lp.n - 1]],
-                }),
-                SearchDir::Right => Some(MapLocation {
-                    m: lp.m,
-                    n: lp.n + 1,
-                    v: self.height_map[[lp.m, lp.n + 1]],
-                }),
-            },
+    /// Binary PPM image of the colour map, each location `scale` pixels wide
+    fn to_ppm(&self, labeled: &BasinLabels, scale: usize) -> Vec<u8> {
+        let colours = self.colour_map(labeled);
+        let (m, n) = colours.dim();
+        let mut out = format!("P6\n{} {}\n255\n", n * scale, m * scale).into_bytes();
+        for row in colours.rows() {
+            for _ in 0..scale {
+                for colour in row {
+                    for _ in 0..scale {
+                        out.extend_from_slice(colour);
+                    }
+                }
+            }
         }
+        out
+    }
+
+    fn write_ppm(&self, labeled: &BasinLabels, scale: usize, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_ppm(labeled, scale))
+    }
+
+    fn get_step(&self, lp: &MapLocation, search_dir: &SearchDir) -> Option<MapLocation> {
+        let (dm, dn) = search_dir.offset();
+        let m = lp.m.checked_add_signed(dm)?;
+        let n = lp.n.checked_add_signed(dn)?;
+        let v = *self.height_map.get([m, n])?;
+        Some(MapLocation { m, n, v })
     }
 }
 
@@ -240,11 +387,62 @@ fn test_daynine_part_two_actual() {
     assert_eq!(part_two(b), 1135260);
 }
 
+#[test]
+fn test_daynine_label_basins() {
+    let input = read_input::read_file("day_nine_test_input.txt");
+    let b = Basin::new(input);
+    let labeled = b.label_basins();
+
+    let sizes: Vec<usize> = labeled.basins.iter().map(|x| x.size).collect();
+    assert_eq!(sizes, vec![3, 9, 14, 9]);
+
+    let mut lows: Vec<MapLocation> = labeled.basins.iter().map(|x| x.low_point).collect();
+    lows.sort_unstable_by_key(|x| (x.m, x.n));
+    assert_eq!(lows, b.low_points);
+
+    assert_eq!(labeled.labels[[0, 0]], Some(0));
+    assert_eq!(labeled.labels[[0, 2]], None);
+    assert_eq!(labeled.labels[[0, 9]], Some(1));
+    assert_eq!(labeled.labels[[4, 9]], Some(3));
+    assert_eq!(labeled.top_product(3), 1134);
+    assert_eq!(labeled.top_product(1), 14);
+    assert_eq!(labeled.top_product(10), 3402);
+}
+
+#[test]
+fn test_daynine_render() {
+    let input = read_input::read_file("day_nine_test_input.txt");
+    let b = Basin::new(input);
+    let labeled = b.label_basins();
+
+    let ansi = b.render_ansi(&labeled);
+    assert_eq!(ansi.lines().count(), 5);
+    assert_eq!(ansi.matches('*').count(), 4);
+    assert_eq!(ansi.matches('#').count(), 15);
+
+    let colours = b.colour_map(&labeled);
+    assert_eq!(colours[[0, 2]], RIDGE_COLOUR);
+    assert_eq!(colours[[0, 1]], LOW_POINT_COLOUR);
+    assert_ne!(colours[[0, 0]], colours[[0, 8]]);
+
+    let ppm = b.to_ppm(&labeled, 2);
+    let header = b"P6\n20 10\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 20 * 10 * 3);
+    // Second pixel row repeats the first
+    let row = 20 * 3;
+    assert_eq!(
+        ppm[header.len()..header.len() + row],
+        ppm[header.len() + row..header.len() + 2 * row]
+    );
+}
+
 #[test]
 fn test_daynine_basin_validate_step() {
     let input = read_input::read_file("day_nine_test_input.txt");
     let b = Basin::new(input);
 
+    use std::collections::HashSet;
     let mut lp1 = HashSet::new();
     lp1.insert(&b.low_points[0]);
 
@@ -281,3 +479,47 @@ fn test_daynine_basin_validate_step() {
         SearchDir::Right
     );
 }
+
+#[test]
+fn test_daynine_basin_rules() {
+    let input = read_input::read_file("day_nine_test_input.txt");
+
+    let eight = BasinRules {
+        wall: 9,
+        neighbourhood: Neighbourhood::Eight,
+    };
+    let b = Basin::with_rules(input.clone(), eight);
+    assert_eq!(b.low_points.len(), 4);
+    let labeled = b.label_basins();
+    // Diagonal gaps in the ridges join every basin into one
+    assert_eq!(labeled.basins.len(), 1);
+    assert_eq!(labeled.basins[0].size, 35);
+    assert_eq!(
+        labeled.basins[0].low_point,
+        MapLocation { m: 0, n: 9, v: 0 }
+    );
+
+    let low_wall = BasinRules {
+        wall: 8,
+        neighbourhood: Neighbourhood::Four,
+    };
+    let b = Basin::with_rules(input, low_wall);
+    let labeled = b.label_basins();
+    assert_eq!(labeled.labels[[2, 7]], None);
+    let mut sizes: Vec<usize> = labeled.basins.iter().map(|x| x.size).collect();
+    sizes.sort_unstable();
+    assert_eq!(sizes, vec![3, 6, 7, 9]);
+}
+
+#[test]
+fn test_daynine_basin_diagonal_step() {
+    let input = read_input::read_file("day_nine_test_input.txt");
+    let b = Basin::new(input);
+
+    assert_eq!(
+        b.get_step(&b.low_points[0], &SearchDir::DownLeft),
+        Some(MapLocation { m: 1, n: 0, v: 3 })
+    );
+    assert_eq!(b.get_step(&b.low_points[0], &SearchDir::UpRight), None);
+    assert_eq!(b.get_step(&b.low_points[1], &SearchDir::DownRight), None);
+}
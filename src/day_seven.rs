@@ -2,6 +2,7 @@
 //! Day Seven - The Treachery of Whales
 #![allow(dead_code)]
 
+use std::collections::HashSet;
 use std::fs;
 use std::time::Instant;
 
@@ -9,92 +10,404 @@ pub(crate) fn day_six_main() {
     println!("\nDay Seven - The Treachery of Whales - Answers");
     let now = Instant::now();
 
-    let input = fs::read_to_string("input/day_seven_input.txt").expect("Error reading file");
-    let mut values: Vec<u32> = input
-        .split(',')
-        .map(|x| x.parse::<u32>().unwrap())
-        .collect();
-    values.sort_unstable();
+    let swarm = read_swarm("input/day_seven_input.txt");
 
-    part_one(&values);
-    part_two(&values);
+    part_one(&swarm);
+    part_two(&swarm);
 
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
-fn part_one(values: &[u32]) -> u32 {
-    let median = values[values.len() / 2];
+/// Crabs as `pos[:weight]` on the first line, optionally followed by a line of
+/// forbidden positions
+fn read_swarm(path: &str) -> CrabSwarm {
+    let input = fs::read_to_string(path).expect("Error reading file");
+    let mut lines = input.lines();
+    CrabSwarm::new(lines.next().unwrap_or(""), lines.next()).expect("Invalid crab input")
+}
 
-    let mut fuel_count = 0_u32;
-    for pos in values {
-        fuel_count += i32::abs(*pos as i32 - median as i32) as u32
-    }
+fn part_one(swarm: &CrabSwarm) -> u64 {
+    // Without forbidden positions the weighted median is optimal for linear fuel
+    let alignment = if swarm.forbidden.is_empty() {
+        swarm
+            .weighted_median()
+            .map(|median| swarm.total_fuel(median, &Linear))
+    } else {
+        swarm.optimize(&Linear).map(|alignment| alignment.fuel)
+    };
+    let fuel_count = alignment.unwrap_or(0);
 
     println!("Part One, Fuel: {}", fuel_count);
     fuel_count
 }
 
-fn part_two(values: &[u32]) -> u32 {
-    let vals_sum: u32 = values.iter().sum();
+fn part_two(swarm: &CrabSwarm) -> u64 {
+    let fuel_count = match swarm.optimize(&Triangular) {
+        Some(alignment) => alignment.fuel,
+        None => 0,
+    };
 
-    // Need to pay more attention to skew, so we switch to mean
-    let raw_mean = vals_sum as f32 / values.len() as f32;
-    let mean = {
-        if f32::abs(raw_mean - raw_mean.round()) < 0.2 {
-            raw_mean.round() as u32
-        } else {
-            raw_mean.round() as u32 - 1
+    println!("Part Two, Fuel: {}", fuel_count);
+    fuel_count
+}
+
+/// Fuel spent by a single crab to travel `distance` positions, which must never
+/// decrease as the distance grows
+trait FuelCost {
+    fn cost(&self, distance: u32) -> u64;
+
+    /// Convex costs sum to a convex total, which allows a binary search over
+    /// positions instead of trying every one of them
+    fn is_convex(&self) -> bool {
+        false
+    }
+}
+
+/// One unit of fuel per step
+struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: u32) -> u64 {
+        distance as u64
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// Each step costs one more than the last, `1 + 2 + ... + distance`
+struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: u32) -> u64 {
+        let distance = distance as u64;
+        distance * (distance + 1) / 2
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+/// User supplied cost that may not be convex. Every allowed position between
+/// the outermost crabs is tried, plus the nearest allowed one beyond each end,
+/// which only finds the best position when the cost never decreases with
+/// distance.
+struct CustomCost<F: Fn(u32) -> u64>(F);
+
+impl<F: Fn(u32) -> u64> FuelCost for CustomCost<F> {
+    fn cost(&self, distance: u32) -> u64 {
+        (self.0)(distance)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Alignment {
+    position: u32,
+    fuel: u64,
+}
+
+/// Crabs sharing a position, `weight` is how many single crab fuel costs they
+/// burn per step
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Crab {
+    position: u32,
+    weight: u32,
+}
+
+impl Crab {
+    /// Parse `pos` or `pos:weight`, an absent weight being one
+    fn new(str_crab: &str) -> Option<Crab> {
+        let mut parts = str_crab.trim().split(':');
+        let position = parts.next()?.trim().parse::<u32>().ok()?;
+        let weight = match parts.next() {
+            Some(w) => w.trim().parse::<u32>().ok()?,
+            None => 1,
+        };
+
+        match parts.next() {
+            Some(_) => None,
+            None => Some(Crab { position, weight }),
         }
-    };
+    }
+}
+
+/// Crabs sorted by position along with the positions they may not align on
+#[derive(Debug)]
+struct CrabSwarm {
+    crabs: Vec<Crab>,
+    forbidden: HashSet<u32>,
+}
 
-    let mut fuel_count = 0_u32;
-    for pos in values {
-        let diff = i32::abs(*pos as i32 - mean as i32) as u32;
-        fuel_count += (diff * (diff + 1)) / 2;
+impl CrabSwarm {
+    fn new(crab_line: &str, forbidden_line: Option<&str>) -> Option<CrabSwarm> {
+        let mut crabs = crab_line
+            .split(',')
+            .map(Crab::new)
+            .collect::<Option<Vec<Crab>>>()?;
+        crabs.sort_unstable_by_key(|c| c.position);
+
+        let forbidden = match forbidden_line {
+            Some(line) if !line.trim().is_empty() => line
+                .split(',')
+                .map(|x| x.trim().parse::<u32>().ok())
+                .collect::<Option<HashSet<u32>>>()?,
+            _ => HashSet::new(),
+        };
+
+        Some(CrabSwarm { crabs, forbidden })
     }
 
-    println!("Part Two, Fuel: {}", fuel_count);
-    fuel_count
+    fn from_positions(values: &[u32]) -> CrabSwarm {
+        let mut crabs: Vec<Crab> = values
+            .iter()
+            .map(|&position| Crab {
+                position,
+                weight: 1,
+            })
+            .collect();
+        crabs.sort_unstable_by_key(|c| c.position);
+
+        CrabSwarm {
+            crabs,
+            forbidden: HashSet::new(),
+        }
+    }
+
+    /// Median position where half the total weight lies at or below it,
+    /// optimal for linear fuel when nothing is forbidden
+    fn weighted_median(&self) -> Option<u32> {
+        let total: u64 = self.crabs.iter().map(|c| c.weight as u64).sum();
+        let mut seen = 0_u64;
+        for crab in &self.crabs {
+            seen += crab.weight as u64;
+            if 2 * seen > total {
+                return Some(crab.position);
+            }
+        }
+        None
+    }
+
+    /// Total fuel for every crab to reach `target`
+    fn total_fuel<C: FuelCost>(&self, target: u32, cost: &C) -> u64 {
+        self.crabs
+            .iter()
+            .map(|c| c.weight as u64 * cost.cost(c.position.abs_diff(target)))
+            .sum()
+    }
+
+    /// Nearest allowed position at or below `pos`
+    fn allowed_below(&self, pos: u32) -> Option<u32> {
+        (0..=pos).rev().find(|p| !self.forbidden.contains(p))
+    }
+
+    /// Nearest allowed position at or above `pos`
+    fn allowed_above(&self, pos: u32) -> Option<u32> {
+        (pos..=u32::MAX).find(|p| !self.forbidden.contains(p))
+    }
+
+    /// Find the cheapest allowed position, lowest position winning ties
+    fn optimize<C: FuelCost>(&self, cost: &C) -> Option<Alignment> {
+        let lo = self.crabs.first()?.position;
+        let hi = self.crabs.last()?.position;
+
+        let candidates: Vec<u32> = if cost.is_convex() {
+            // Convex total, so the first position that is no worse than its
+            // right neighbour is a minimum. Past it the cost only grows, so the
+            // nearest allowed position either side is the constrained best.
+            let (mut lo, mut hi) = (lo, hi);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if self.total_fuel(mid, cost) <= self.total_fuel(mid + 1, cost) {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
+            }
+            [self.allowed_below(lo), self.allowed_above(lo)]
+                .into_iter()
+                .flatten()
+                .collect()
+        } else {
+            // Try every allowed position between the outermost crabs. Beyond
+            // them every crab only gets further away, so with a cost that never
+            // decreases the nearest allowed position past each end is enough.
+            (lo..=hi)
+                .filter(|p| !self.forbidden.contains(p))
+                .chain(lo.checked_sub(1).and_then(|p| self.allowed_below(p)))
+                .chain(hi.checked_add(1).and_then(|p| self.allowed_above(p)))
+                .collect()
+        };
+
+        let position = candidates
+            .into_iter()
+            .min_by_key(|&p| (self.total_fuel(p, cost), p))?;
+
+        Some(Alignment {
+            position,
+            fuel: self.total_fuel(position, cost),
+        })
+    }
+}
+
+/// Find the cheapest position for equally weighted crabs with nothing
+/// forbidden
+fn optimize<C: FuelCost>(values: &[u32], cost: &C) -> Option<Alignment> {
+    CrabSwarm::from_positions(values).optimize(cost)
 }
 
 #[test]
 fn test_dayseven_part_one() {
-    let input = fs::read_to_string("input/day_seven_test_input.txt").expect("Error reading file");
-    let mut values: Vec<u32> = input
-        .split(',')
-        .map(|x| x.parse::<u32>().unwrap())
-        .collect();
-    values.sort_unstable();
+    let swarm = read_swarm("input/day_seven_test_input.txt");
+    assert_eq!(part_one(&swarm), 37);
 
-    assert_eq!(part_one(&values), 37);
+    let swarm = read_swarm("input/day_seven_input.txt");
+    assert_eq!(part_one(&swarm), 343441);
+}
 
-    let input = fs::read_to_string("input/day_seven_input.txt").expect("Error reading file");
-    let mut values: Vec<u32> = input
-        .split(',')
-        .map(|x| x.parse::<u32>().unwrap())
-        .collect();
-    values.sort_unstable();
+#[test]
+fn test_dayseven_part_two() {
+    let swarm = read_swarm("input/day_seven_test_input.txt");
+    assert_eq!(part_two(&swarm), 168);
 
-    assert_eq!(part_one(&values), 343441);
+    let swarm = read_swarm("input/day_seven_input.txt");
+    assert_eq!(part_two(&swarm), 98925151);
 }
 
 #[test]
-fn test_dayseven_part_two() {
-    let input = fs::read_to_string("input/day_seven_test_input.txt").expect("Error reading file");
-    let mut values: Vec<u32> = input
-        .split(',')
-        .map(|x| x.parse::<u32>().unwrap())
-        .collect();
-    values.sort_unstable();
+fn test_dayseven_weighted_input() {
+    let swarm = read_swarm("input/day_seven_weighted_test_input.txt");
+    assert_eq!(swarm.crabs.len(), 3);
+    assert!(swarm.forbidden.contains(&10));
+
+    // The heavy crab would pull everyone to 10, which is forbidden
+    assert_eq!(part_one(&swarm), 19);
+    assert_eq!(part_two(&swarm), 61);
+}
+
+#[test]
+fn test_dayseven_optimize_costs() {
+    let values = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
 
-    assert_eq!(part_two(&values), 168);
+    assert_eq!(
+        optimize(&values, &Linear),
+        Some(Alignment {
+            position: 2,
+            fuel: 37
+        })
+    );
+    assert_eq!(
+        optimize(&values, &Triangular),
+        Some(Alignment {
+            position: 5,
+            fuel: 168
+        })
+    );
+    assert_eq!(
+        optimize(&values, &CustomCost(|d| (d as u64).pow(2))),
+        Some(Alignment {
+            position: 5,
+            fuel: 291
+        })
+    );
+    assert_eq!(
+        optimize(&values, &CustomCost(|d| if d == 0 { 0 } else { 1 })),
+        Some(Alignment {
+            position: 2,
+            fuel: 7
+        })
+    );
+    assert_eq!(optimize(&[], &Linear), None);
+}
 
+#[test]
+fn test_dayseven_optimize_matches_brute_force() {
     let input = fs::read_to_string("input/day_seven_input.txt").expect("Error reading file");
-    let mut values: Vec<u32> = input
+    let values: Vec<u32> = input
         .split(',')
-        .map(|x| x.parse::<u32>().unwrap())
+        .map(|x| x.trim().parse::<u32>().unwrap())
         .collect();
-    values.sort_unstable();
 
-    assert_eq!(part_two(&values), 98925151);
+    let exact = optimize(&values, &Triangular).unwrap();
+    let brute = optimize(&values, &CustomCost(|d| Triangular.cost(d))).unwrap();
+    assert_eq!(exact, brute);
+}
+
+#[test]
+fn test_dayseven_crab_new() {
+    assert_eq!(
+        Crab::new("16"),
+        Some(Crab {
+            position: 16,
+            weight: 1
+        })
+    );
+    assert_eq!(
+        Crab::new(" 4:3 "),
+        Some(Crab {
+            position: 4,
+            weight: 3
+        })
+    );
+    assert_eq!(Crab::new("4:"), None);
+    assert_eq!(Crab::new("4:3:2"), None);
+    assert_eq!(Crab::new("x"), None);
+}
+
+#[test]
+fn test_dayseven_weighted_swarm() {
+    let swarm = CrabSwarm::new("0,10:3,2", None).unwrap();
+    assert_eq!(swarm.weighted_median(), Some(10));
+    assert_eq!(
+        swarm.optimize(&Linear),
+        Some(Alignment {
+            position: 10,
+            fuel: 18
+        })
+    );
+
+    let unit = CrabSwarm::from_positions(&[16, 1, 2, 0, 4, 2, 7, 1, 2, 14]);
+    let median = unit.weighted_median().unwrap();
+    assert_eq!(unit.optimize(&Linear).unwrap().fuel, 37);
+    assert_eq!(unit.total_fuel(median, &Linear), 37);
+}
+
+#[test]
+fn test_dayseven_constrained_swarm() {
+    let swarm = CrabSwarm::new("16,1,2,0,4,2,7,1,2,14", Some("2,3")).unwrap();
+    assert_eq!(
+        swarm.optimize(&Linear),
+        Some(Alignment {
+            position: 1,
+            fuel: 41
+        })
+    );
+    assert_eq!(
+        swarm.optimize(&Triangular),
+        Some(Alignment {
+            position: 5,
+            fuel: 168
+        })
+    );
+    assert_eq!(
+        swarm.optimize(&CustomCost(|d| d as u64)),
+        swarm.optimize(&Linear)
+    );
+
+    // Every position between the crabs is forbidden, so they must leave it
+    let boxed = CrabSwarm::new("1:2,3", Some("0,1,2,3")).unwrap();
+    assert_eq!(
+        boxed.optimize(&Linear),
+        Some(Alignment {
+            position: 4,
+            fuel: 7
+        })
+    );
+    assert_eq!(
+        boxed.optimize(&CustomCost(|d| d as u64)),
+        boxed.optimize(&Linear)
+    );
+
+    assert!(CrabSwarm::new("1,2", Some("a")).is_none());
 }
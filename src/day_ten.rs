@@ -2,8 +2,8 @@
 //! Day Ten - Syntax Scoring
 #![allow(dead_code)]
 
-use std::collections::HashMap;
 use std::time::Instant;
+use std::{fmt, fs, io};
 
 use crate::read_input;
 
@@ -19,48 +19,70 @@ pub(crate) fn day_ten_main() {
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
-fn part_one(lines: &[String]) -> u32 {
-    let match_map = HashMap::from([(')', '('), (']', '['), ('}', '{'), ('>', '<')]);
+pub(crate) fn lint_main(args: &[String]) {
+    let profile = match args.first().map(String::as_str) {
+        Some("rust") => LanguageProfile::Rust,
+        Some("json") => LanguageProfile::Json,
+        _ => {
+            println!("Usage: lint <rust|json> <file>...");
+            return;
+        }
+    };
 
-    let mut unmatched = Vec::new();
-    for line in lines {
-        let mut parse_queue = Vec::new();
-        for c in line.chars() {
-            match c {
-                '(' | '[' | '{' | '<' => parse_queue.push(c),
-                ')' | ']' | '}' | '>'
-                    if *match_map.get(&c).unwrap() == parse_queue.pop().unwrap() => {}
-                _ => unmatched.push(c),
+    for path in &args[1..] {
+        match lint_file(path, profile) {
+            Ok(issues) => {
+                for issue in issues {
+                    println!("{path}:{issue}");
+                }
             }
+            Err(err) => println!("{path}: {err}"),
         }
     }
+}
+
+/// Part one points for a corrupting closer
+fn corruption_score(c: char) -> Option<u32> {
+    match c {
+        ')' => Some(3),
+        ']' => Some(57),
+        '}' => Some(1197),
+        '>' => Some(25137),
+        _ => None,
+    }
+}
+
+/// Part two points for each closer of a completion
+fn completion_score(c: char) -> Option<u64> {
+    match c {
+        ')' => Some(1),
+        ']' => Some(2),
+        '}' => Some(3),
+        '>' => Some(4),
+        _ => None,
+    }
+}
+
+fn part_one(lines: &[String]) -> u32 {
+    let delims = Delimiters::brackets();
 
-    let score_map: HashMap<char, u32> =
-        HashMap::from([(')', 3), (']', 57), ('}', 1197), ('>', 25137)]);
     let mut score = 0_u32;
-    for val in unmatched {
-        score += score_map.get(&val).unwrap();
+    for line in lines {
+        if let Diagnostic::Corrupted { found, .. } = delims.check(line) {
+            score += corruption_score(found).unwrap();
+        }
     }
     println!("Score: {}", score);
     score
 }
 
 fn part_two(lines: Vec<String>) -> u64 {
-    let match_map = HashMap::from([('(', ")"), ('[', "]"), ('{', "}"), ('<', ">")]);
-    let score_map: HashMap<char, u64> = HashMap::from([(')', 1), (']', 2), ('}', 3), ('>', 4)]);
+    let delims = Delimiters::brackets();
 
     let mut scores = Vec::new();
     for line in lines {
-        let mut sb = "".to_string();
-        if let Some(incomplete) = parse_line(&line) {
-            for c in incomplete {
-                sb.push_str(match_map.get(&c).unwrap());
-            }
-            let mut score = 0_u64;
-            for ch in sb.chars().rev() {
-                score = score * 5 + score_map.get(&ch).unwrap();
-            }
-            scores.push(score)
+        if let Diagnostic::Incomplete { completion } = delims.check(&line) {
+            scores.push(completion_total(&completion).unwrap())
         }
     }
     scores.sort_unstable();
@@ -69,18 +91,414 @@ fn part_two(lines: Vec<String>) -> u64 {
     score
 }
 
-fn parse_line(line: &str) -> Option<Vec<char>> {
-    let match_map = HashMap::from([(')', '('), (']', '['), ('}', '{'), ('>', '<')]);
+/// Part two, but corrupted lines a single edit turns incomplete are scored too
+fn part_two_repaired(lines: &[String]) -> u64 {
+    let delims = Delimiters::brackets();
 
-    let mut parse_queue = Vec::new();
-    for c in line.chars() {
-        match c {
-            '(' | '[' | '{' | '<' => parse_queue.push(c),
-            ')' | ']' | '}' | '>' if *match_map.get(&c).unwrap() == parse_queue.pop().unwrap() => {}
+    let mut scores = Vec::new();
+    for line in lines {
+        let completion = match delims.check(line) {
+            Diagnostic::Incomplete { completion } => completion,
+            Diagnostic::Corrupted { .. } => match delims.repair(line) {
+                Some(repair) if !repair.completion.is_empty() => repair.completion,
+                _ => continue,
+            },
+            Diagnostic::Valid => continue,
+        };
+        scores.push(completion_total(&completion).unwrap());
+    }
+    scores.sort_unstable();
+    let score = scores[scores.len() / 2];
+    println!("Repaired Score: {}", score);
+    score
+}
+
+/// Part two score of a whole completion string
+fn completion_total(completion: &str) -> Option<u64> {
+    completion
+        .chars()
+        .try_fold(0_u64, |score, ch| Some(score * 5 + completion_score(ch)?))
+}
+
+/// Single character change to a line, `column` counting from one
+#[derive(Debug, PartialEq)]
+enum Edit {
+    Replace { column: usize, from: char, to: char },
+    Delete { column: usize, found: char },
+}
+
+/// Edit salvaging a corrupted line and what then completes it
+#[derive(Debug, PartialEq)]
+struct Repair {
+    edit: Edit,
+    completion: String,
+}
+
+impl Repair {
+    fn score(&self) -> Option<u64> {
+        completion_total(&self.completion)
+    }
+}
+
+/// Outcome of checking a single line
+#[derive(Debug, PartialEq)]
+enum Diagnostic {
+    Valid,
+    /// First closer not matching the innermost open delimiter. `column` counts
+    /// characters from one and `expected` is `None` when nothing was open.
+    Corrupted {
+        column: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    /// Every closer matched, but `completion` is still needed to close the rest
+    Incomplete {
+        completion: String,
+    },
+}
+
+/// Opening and closing delimiter pairs, anything else on a line is ignored
+#[derive(Debug)]
+struct Delimiters {
+    pairs: Vec<(char, char)>,
+}
+
+impl Delimiters {
+    /// Each character may only appear once across all pairs
+    fn new(pairs: &[(char, char)]) -> Option<Delimiters> {
+        let mut seen = Vec::new();
+        for &(open, close) in pairs {
+            if open == close || seen.contains(&open) || seen.contains(&close) {
+                return None;
+            }
+            seen.push(open);
+            seen.push(close);
+        }
+
+        Some(Delimiters {
+            pairs: pairs.to_vec(),
+        })
+    }
+
+    /// The four bracket pairs of the puzzle
+    fn brackets() -> Delimiters {
+        Delimiters::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')]).unwrap()
+    }
+
+    fn closer(&self, open: char) -> Option<char> {
+        self.pairs.iter().find(|p| p.0 == open).map(|p| p.1)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|p| p.1 == c)
+    }
+
+    /// Find the single character edit that leaves a corrupted line no longer
+    /// corrupted. Only characters up to the corruption can fix it. Edits
+    /// leaving a valid line win, then those needing the shortest completion,
+    /// then those nearest the corruption, replacing before deleting, so a
+    /// stray closer is deleted while a wrong closer is swapped for the
+    /// expected one.
+    fn repair(&self, line: &str) -> Option<Repair> {
+        let column = match self.check(line) {
+            Diagnostic::Corrupted { column, .. } => column,
             _ => return None,
+        };
+
+        let chars: Vec<char> = line.chars().collect();
+        let symbols: Vec<char> = self.pairs.iter().flat_map(|&(o, c)| [o, c]).collect();
+        let mut best: Option<((usize, usize, usize), Repair)> = None;
+        for i in (0..column).rev() {
+            let from = chars[i];
+            let replacements = symbols.iter().filter(|&&to| to != from).map(|&to| {
+                let mut edited = chars.clone();
+                edited[i] = to;
+                (
+                    Edit::Replace {
+                        column: i + 1,
+                        from,
+                        to,
+                    },
+                    edited,
+                )
+            });
+            let deletion = {
+                let mut edited = chars.clone();
+                edited.remove(i);
+                (
+                    Edit::Delete {
+                        column: i + 1,
+                        found: from,
+                    },
+                    edited,
+                )
+            };
+
+            for (order, (edit, edited)) in replacements.chain([deletion]).enumerate() {
+                let edited: String = edited.into_iter().collect();
+                let completion = match self.check(&edited) {
+                    Diagnostic::Corrupted { .. } => continue,
+                    Diagnostic::Valid => String::new(),
+                    Diagnostic::Incomplete { completion } => completion,
+                };
+                let rank = (completion.chars().count(), column - i, order);
+                if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+                    best = Some((rank, Repair { edit, completion }));
+                }
+            }
+        }
+        best.map(|(_, repair)| repair)
+    }
+
+    fn check(&self, line: &str) -> Diagnostic {
+        let mut expect_stack = Vec::new();
+        for (i, c) in line.chars().enumerate() {
+            if let Some(close) = self.closer(c) {
+                expect_stack.push(close);
+            } else if self.is_closer(c) {
+                match expect_stack.pop() {
+                    Some(expected) if expected == c => {}
+                    expected => {
+                        return Diagnostic::Corrupted {
+                            column: i + 1,
+                            expected,
+                            found: c,
+                        };
+                    }
+                }
+            }
+        }
+
+        if expect_stack.is_empty() {
+            Diagnostic::Valid
+        } else {
+            Diagnostic::Incomplete {
+                completion: expect_stack.iter().rev().collect(),
+            }
+        }
+    }
+}
+
+/// Source language rules for finding the code between strings and comments
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LanguageProfile {
+    Rust,
+    Json,
+}
+
+impl LanguageProfile {
+    fn delimiters(&self) -> Delimiters {
+        match self {
+            LanguageProfile::Rust => {
+                Delimiters::new(&[('(', ')'), ('[', ']'), ('{', '}')]).unwrap()
+            }
+            LanguageProfile::Json => Delimiters::new(&[('[', ']'), ('{', '}')]).unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum LintKind {
+    /// A closer for something other than the innermost open delimiter
+    Mismatched { expected: char, found: char },
+    /// A closer with nothing open
+    Unexpected(char),
+    /// An opener never closed, `expected` being its closer
+    Unclosed { open: char, expected: char },
+}
+
+/// Unbalanced delimiter, with line and column counted from one
+#[derive(Debug, PartialEq)]
+struct LintIssue {
+    line: usize,
+    column: usize,
+    kind: LintKind,
+}
+
+impl LintIssue {
+    /// Puzzle score of the offending delimiter, if it is one the puzzle scores
+    fn severity(&self) -> Option<u64> {
+        match self.kind {
+            LintKind::Mismatched { found, .. } | LintKind::Unexpected(found) => {
+                corruption_score(found).map(u64::from)
+            }
+            LintKind::Unclosed { expected, .. } => completion_score(expected),
+        }
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match self.kind {
+            LintKind::Mismatched { expected, found } => {
+                write!(f, "expected `{expected}`, found `{found}`")?
+            }
+            LintKind::Unexpected(found) => write!(f, "unexpected `{found}`")?,
+            LintKind::Unclosed { open, expected } => {
+                write!(f, "unclosed `{open}`, expected `{expected}`")?
+            }
+        }
+        match self.severity() {
+            Some(score) => write!(f, " (severity {score})"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn lint_file(path: &str, profile: LanguageProfile) -> io::Result<Vec<LintIssue>> {
+    Ok(lint(&fs::read_to_string(path)?, profile))
+}
+
+/// Check delimiters across a whole text. A closer matching something further
+/// down the stack closes everything above it, so one slip is reported once
+/// rather than on every later closer.
+fn lint(text: &str, profile: LanguageProfile) -> Vec<LintIssue> {
+    let delims = profile.delimiters();
+    let mut issues = Vec::new();
+    let mut open_stack: Vec<(usize, usize, char, char)> = Vec::new();
+
+    for (line, column, c) in code_chars(text, profile) {
+        if let Some(close) = delims.closer(c) {
+            open_stack.push((line, column, c, close));
+        } else if delims.is_closer(c) {
+            let kind = match (open_stack.last(), open_stack.iter().rposition(|o| o.3 == c)) {
+                (_, Some(depth)) => {
+                    let expected = open_stack[open_stack.len() - 1].3;
+                    open_stack.truncate(depth);
+                    if expected == c {
+                        continue;
+                    }
+                    LintKind::Mismatched { expected, found: c }
+                }
+                (Some(top), None) => LintKind::Mismatched {
+                    expected: top.3,
+                    found: c,
+                },
+                (None, None) => LintKind::Unexpected(c),
+            };
+            issues.push(LintIssue { line, column, kind });
         }
     }
-    Some(parse_queue)
+
+    for (line, column, open, expected) in open_stack {
+        issues.push(LintIssue {
+            line,
+            column,
+            kind: LintKind::Unclosed { open, expected },
+        });
+    }
+    issues
+}
+
+/// Characters outside strings and comments, with their line and column
+fn code_chars(text: &str, profile: LanguageProfile) -> Vec<(usize, usize, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut positions = Vec::with_capacity(chars.len());
+    let (mut line, mut column) = (1, 1);
+    for &c in &chars {
+        positions.push((line, column));
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+
+    let is_ident = |i: Option<usize>| {
+        i.and_then(|i| chars.get(i))
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+    };
+
+    let mut code = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match (profile, &chars[i..]) {
+            (_, ['"', ..]) => i = skip_string(&chars, i + 1),
+            (LanguageProfile::Rust, ['/', '/', ..]) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            (LanguageProfile::Rust, ['/', '*', ..]) => i = skip_block_comment(&chars, i + 2),
+            (LanguageProfile::Rust, ['r', '"' | '#', ..])
+                if !is_ident(i.checked_sub(1))
+                    || (chars[i - 1] == 'b' && !is_ident(i.checked_sub(2))) =>
+            {
+                let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+                if chars.get(i + 1 + hashes) == Some(&'"') {
+                    i = skip_raw_string(&chars, i + 2 + hashes, hashes);
+                } else {
+                    i += 1;
+                }
+            }
+            (LanguageProfile::Rust, ['\'', ..]) => i = skip_char_literal(&chars, i),
+            (_, [c, ..]) => {
+                code.push((positions[i].0, positions[i].1, *c));
+                i += 1;
+            }
+            (_, []) => unreachable!(),
+        }
+    }
+    code
+}
+
+/// Index after the closing quote of a string whose body starts at `i`
+fn skip_string(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// Index after the end of a possibly nested block comment whose body starts at
+/// `i`
+fn skip_block_comment(chars: &[char], mut i: usize) -> usize {
+    let mut depth = 1;
+    while i < chars.len() && depth > 0 {
+        match &chars[i..] {
+            ['/', '*', ..] => {
+                depth += 1;
+                i += 2;
+            }
+            ['*', '/', ..] => {
+                depth -= 1;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    i.min(chars.len())
+}
+
+/// Index after the closing quote and hashes of a raw string whose body starts
+/// at `i`
+fn skip_raw_string(chars: &[char], mut i: usize, hashes: usize) -> usize {
+    while i < chars.len() {
+        if chars[i] == '"' && chars[i + 1..].iter().take_while(|&&h| h == '#').count() >= hashes {
+            return i + 1 + hashes;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Index after a char literal starting at the quote `i`, or just past the quote
+/// when it begins a lifetime or label instead
+fn skip_char_literal(chars: &[char], i: usize) -> usize {
+    match &chars[i + 1..] {
+        ['\\', rest @ ..] => match rest.iter().skip(1).position(|&c| c == '\'') {
+            Some(end) => i + end + 4,
+            None => chars.len(),
+        },
+        [_, '\'', ..] => i + 3,
+        _ => i + 1,
+    }
 }
 
 #[test]
@@ -106,3 +524,188 @@ fn test_dayten_part_two_actual() {
     let input = read_input::read_file("day_ten_input.txt");
     assert_eq!(part_two(input), 1118976874);
 }
+
+#[test]
+fn test_dayten_delimiters_check() {
+    let delims = Delimiters::brackets();
+
+    assert_eq!(delims.check("([]){<>}"), Diagnostic::Valid);
+    assert_eq!(
+        delims.check("{([(<{}[<>[]}>{[]{[(<()>"),
+        Diagnostic::Corrupted {
+            column: 13,
+            expected: Some(']'),
+            found: '}'
+        }
+    );
+    assert_eq!(
+        delims.check(")("),
+        Diagnostic::Corrupted {
+            column: 1,
+            expected: None,
+            found: ')'
+        }
+    );
+    assert_eq!(
+        delims.check("[({(<(())[]>[[{[]{<()<>>"),
+        Diagnostic::Incomplete {
+            completion: "}}]])})]".to_string()
+        }
+    );
+    assert_eq!(
+        delims.check("fn x(a: [u8; 2]) {"),
+        Diagnostic::Incomplete {
+            completion: "}".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_dayten_delimiters_custom() {
+    assert!(Delimiters::new(&[('|', '|')]).is_none());
+    assert!(Delimiters::new(&[('(', ')'), ('[', ')')]).is_none());
+
+    let delims = Delimiters::new(&[('«', '»'), ('(', ')')]).unwrap();
+    assert_eq!(
+        delims.check("«(»"),
+        Diagnostic::Corrupted {
+            column: 3,
+            expected: Some(')'),
+            found: '»'
+        }
+    );
+    assert_eq!(delims.check("«[»"), Diagnostic::Valid);
+}
+
+#[test]
+fn test_dayten_lint_rust() {
+    let text = "fn main() {\n    let s = \"(}\\\"\"; // [\n    let c = ')';\n    /* { /* ] */ */\n    let r = r#\"\"{\"#;\n    x::<'a>(b'[', '\\'', '\\u{5b}')\n}\n";
+    assert_eq!(lint(text, LanguageProfile::Rust), vec![]);
+
+    let text = "fn f() {\n    g(a[1)];\n";
+    assert_eq!(
+        lint(text, LanguageProfile::Rust),
+        vec![
+            LintIssue {
+                line: 2,
+                column: 10,
+                kind: LintKind::Mismatched {
+                    expected: ']',
+                    found: ')'
+                }
+            },
+            LintIssue {
+                line: 2,
+                column: 11,
+                kind: LintKind::Mismatched {
+                    expected: '}',
+                    found: ']'
+                }
+            },
+            LintIssue {
+                line: 1,
+                column: 8,
+                kind: LintKind::Unclosed {
+                    open: '{',
+                    expected: '}'
+                }
+            },
+        ]
+    );
+
+    // This file is full of brackets in strings and char literals
+    assert_eq!(
+        lint_file("src/day_ten.rs", LanguageProfile::Rust).unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn test_dayten_lint_json() {
+    let text = "{\"a\": [1, \"]\"],\n \"b\": {}}}";
+    let issues = lint(text, LanguageProfile::Json);
+    assert_eq!(
+        issues,
+        vec![LintIssue {
+            line: 2,
+            column: 10,
+            kind: LintKind::Unexpected('}')
+        }]
+    );
+    assert_eq!(issues[0].severity(), Some(1197));
+    assert_eq!(
+        issues[0].to_string(),
+        "2:10: unexpected `}` (severity 1197)"
+    );
+
+    let issues = lint("[{", LanguageProfile::Json);
+    assert_eq!(issues[0].severity(), Some(2));
+    assert_eq!(
+        issues[1].to_string(),
+        "1:2: unclosed `{`, expected `}` (severity 3)"
+    );
+}
+
+#[test]
+fn test_dayten_repair() {
+    let delims = Delimiters::brackets();
+
+    let repair = delims.repair("{([(<{}[<>[]}>{[]{[(<()>").unwrap();
+    assert_eq!(
+        repair.edit,
+        Edit::Replace {
+            column: 13,
+            from: '}',
+            to: ']'
+        }
+    );
+    assert_eq!(repair.completion, ")]}})])}");
+    assert_eq!(repair.score(), Some(120808));
+
+    // Nothing is open for the stray closer, so deleting it leaves a valid line
+    assert_eq!(
+        delims.repair("())"),
+        Some(Repair {
+            edit: Edit::Delete {
+                column: 3,
+                found: ')'
+            },
+            completion: String::new()
+        })
+    );
+
+    // Two separate corruptions need two edits
+    assert_eq!(delims.repair("(]<}"), None);
+    assert_eq!(delims.repair("(("), None);
+}
+
+#[test]
+fn test_dayten_part_two_repaired() {
+    let input = read_input::read_file("day_ten_test_input.txt");
+    let delims = Delimiters::brackets();
+    for line in &input {
+        if let Diagnostic::Corrupted { .. } = delims.check(line) {
+            let repair = delims.repair(line).unwrap();
+            let edited = match repair.edit {
+                Edit::Replace { column, to, .. } => {
+                    let mut chars: Vec<char> = line.chars().collect();
+                    chars[column - 1] = to;
+                    chars.into_iter().collect::<String>()
+                }
+                Edit::Delete { column, .. } => {
+                    let mut chars: Vec<char> = line.chars().collect();
+                    chars.remove(column - 1);
+                    chars.into_iter().collect::<String>()
+                }
+            };
+            assert_eq!(
+                delims.check(&edited),
+                Diagnostic::Incomplete {
+                    completion: repair.completion
+                }
+            );
+        }
+    }
+
+    assert_eq!(part_two_repaired(&input), 120808);
+}
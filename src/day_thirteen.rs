@@ -2,8 +2,10 @@
 //! Day Thirteen - Transparent Origami
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::fs;
+use std::io::{self, Write};
 use std::time::Instant;
 
 use crate::read_input;
@@ -19,25 +21,84 @@ pub(crate) fn day_thirteen_main() {
         hs.insert(Coordinate::new(x).unwrap());
         hs
     });
-    let mut instrs: Vec<_> = seps[1]
+    let instrs: Vec<_> = seps[1]
         .iter()
         .map(|x| FoldDirection::new(x).unwrap())
         .collect();
 
-    let mut origami = PaperOrigami::new(cords_set).unwrap();
-
-    origami = origami.fold(instrs.remove(0)).unwrap();
-    println!("Part One, First Fold Cords: {}", origami.cords.len());
-
+    let origami = PaperOrigami::new(cords_set).unwrap();
+    let mut session = FoldSession::new(origami, LineDots::Reject);
     for inst in instrs {
-        origami = origami.fold(inst).unwrap();
+        session.apply(inst).unwrap();
+    }
+    println!("Part One, First Fold Cords: {}", session.history[0].dots);
+
+    match session.origami.read_letters() {
+        Ok(code) => println!("Part Two, Code: {}", code),
+        Err(e) => println!("Part Two, {:?}\n{}", e, session.origami),
     }
-    println!("Part Two, {}", origami);
 
     println!("Execution time: {}ms", now.elapsed().as_millis());
 }
 
+/// Rows of the block letters the folded paper spells out
+const GLYPH_HEIGHT: usize = 6;
+/// Columns of a block letter, each followed by one blank column
+const GLYPH_WIDTH: usize = 4;
+
+/// The puzzle's letters that fit the 4x6 grid. Its Y is five columns wide, so
+/// it is left out rather than matched by a cut down copy.
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[derive(Debug, PartialEq)]
+enum OcrError {
+    /// The paper is not exactly one glyph tall
+    Height(usize),
+    /// Glyphs matching no letter, by index from the left, alongside the text
+    /// read with `?` in their place
+    Unrecognised { text: String, positions: Vec<usize> },
+}
+
+/// What happens to dots lying on a fold line
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LineDots {
+    /// They vanish into the crease
+    Drop,
+    /// The fold fails
+    Reject,
+}
+
 #[derive(Debug, PartialEq)]
+enum FoldError {
+    DotOnLine(Coordinate),
+    /// The fold line lies past the last row or column of the paper
+    OutsidePaper {
+        line: usize,
+        size: usize,
+    },
+    /// Every dot was dropped on the fold line
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum FoldDirection {
     Horizontal(usize),
     Vertical(usize),
@@ -46,7 +107,6 @@ enum FoldDirection {
 impl FoldDirection {
     fn new(str_dir: &str) -> Option<FoldDirection> {
         let iso_dir = str_dir
-            .trim()
             .split_whitespace()
             .find(|&x| x.starts_with("x=") | x.starts_with("y="));
 
@@ -70,6 +130,15 @@ impl FoldDirection {
     }
 }
 
+impl fmt::Display for FoldDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FoldDirection::Horizontal(col) => write!(f, "fold along x={}", col),
+            FoldDirection::Vertical(row) => write!(f, "fold along y={}", row),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
 struct Coordinate {
     m: usize,
@@ -95,12 +164,16 @@ impl Coordinate {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 struct PaperOrigami {
     cords: HashSet<Coordinate>,
     paper: Vec<Vec<char>>,
     size_m: usize,
     size_n: usize,
+    /// Rows and columns of the sheet itself, which folds can leave larger than
+    /// the dots on it
+    sheet_m: usize,
+    sheet_n: usize,
 }
 
 impl PaperOrigami {
@@ -133,58 +206,320 @@ impl PaperOrigami {
             paper,
             size_m,
             size_n,
+            sheet_m: size_m,
+            sheet_n: size_n,
         })
     }
 
-    fn fold(&self, fold_dir: FoldDirection) -> Option<PaperOrigami> {
-        match fold_dir {
-            FoldDirection::Horizontal(index) => Some(self.fold_horizontal(index).unwrap()),
-            FoldDirection::Vertical(index) => Some(self.fold_vertical(index).unwrap()),
-        }
+    /// Fold, refusing dots that lie on the fold line
+    fn fold(&self, fold_dir: FoldDirection) -> Result<PaperOrigami, FoldError> {
+        self.fold_with(fold_dir, LineDots::Reject)
     }
 
-    fn fold_horizontal(&self, fold_col: usize) -> Option<PaperOrigami> {
-        let mut new_cords: HashSet<Coordinate> = HashSet::with_capacity(self.cords.len());
+    fn fold_with(
+        &self,
+        fold_dir: FoldDirection,
+        line_dots: LineDots,
+    ) -> Result<PaperOrigami, FoldError> {
+        self.fold_mapped(fold_dir, line_dots)
+            .map(|(origami, _)| origami)
+    }
 
-        use std::cmp::Ordering::*;
-        for cord in &self.cords {
-            match cord.n.cmp(&fold_col) {
-                Less => {
-                    new_cords.insert(*cord);
-                }
-                Greater => {
-                    new_cords.insert(Coordinate {
-                        m: cord.m,
-                        n: cord.n - 2 * (cord.n - fold_col),
-                    });
-                }
-                _ => unreachable!(),
+    /// Fold, also giving where each dot of the folded paper came from
+    fn fold_mapped(
+        &self,
+        fold_dir: FoldDirection,
+        line_dots: LineDots,
+    ) -> Result<(PaperOrigami, FoldMap), FoldError> {
+        let mut map = match fold_dir {
+            FoldDirection::Horizontal(fold_col) => fold_axis(
+                &self.cords,
+                fold_col,
+                self.sheet_n,
+                line_dots,
+                |cord| cord.n,
+                |cord, n| Coordinate { m: cord.m, n },
+            )?,
+            FoldDirection::Vertical(fold_row) => fold_axis(
+                &self.cords,
+                fold_row,
+                self.sheet_m,
+                line_dots,
+                |cord| cord.m,
+                |cord, m| Coordinate { m, n: cord.n },
+            )?,
+        };
+        map.sheet = (self.sheet_m, self.sheet_n);
+
+        let mut origami =
+            PaperOrigami::new(map.sources.keys().copied().collect()).ok_or(FoldError::Empty)?;
+        (origami.sheet_m, origami.sheet_n) = match fold_dir {
+            FoldDirection::Horizontal(fold_col) => {
+                (self.sheet_m, folded_size(fold_col, self.sheet_n))
+            }
+            FoldDirection::Vertical(fold_row) => {
+                (folded_size(fold_row, self.sheet_m), self.sheet_n)
             }
+        };
+        Ok((origami, map))
+    }
+
+    /// Fold the right half over the left along `fold_col`
+    fn fold_horizontal(
+        &self,
+        fold_col: usize,
+        line_dots: LineDots,
+    ) -> Result<PaperOrigami, FoldError> {
+        self.fold_with(FoldDirection::Horizontal(fold_col), line_dots)
+    }
+
+    /// Fold the bottom half up over the top along `fold_row`
+    fn fold_vertical(
+        &self,
+        fold_row: usize,
+        line_dots: LineDots,
+    ) -> Result<PaperOrigami, FoldError> {
+        self.fold_with(FoldDirection::Vertical(fold_row), line_dots)
+    }
+
+    /// Reverse a fold, putting each dot back where it came from
+    fn unfold(&self, map: &FoldMap) -> PaperOrigami {
+        let cords = self
+            .cords
+            .iter()
+            .flat_map(|cord| &map.sources[cord])
+            .chain(&map.dropped)
+            .copied()
+            .collect();
+        let mut origami =
+            PaperOrigami::new(cords).expect("Unfolded paper has the dots it was folded from");
+        (origami.sheet_m, origami.sheet_n) = map.sheet;
+        origami
+    }
+}
+
+/// Where the dots of a folded paper came from
+#[derive(Clone, Debug, PartialEq)]
+struct FoldMap {
+    /// Each folded dot against the one or two dots landing on it
+    sources: HashMap<Coordinate, Vec<Coordinate>>,
+    /// Dots lost on the fold line
+    dropped: Vec<Coordinate>,
+    /// Rows and columns of the sheet before folding
+    sheet: (usize, usize),
+}
+
+/// Length of a sheet of `size` along the fold axis once folded at `line`, the
+/// longer of the two sides
+fn folded_size(line: usize, size: usize) -> usize {
+    line.max(size - 1 - line)
+}
+
+/// Reflect every coordinate past `line` back over it along one axis. When the
+/// far side is longer than the near side, everything shifts along so the
+/// reflected dots stay at or above zero.
+fn fold_axis(
+    cords: &HashSet<Coordinate>,
+    line: usize,
+    size: usize,
+    line_dots: LineDots,
+    get: impl Fn(&Coordinate) -> usize,
+    set: impl Fn(&Coordinate, usize) -> Coordinate,
+) -> Result<FoldMap, FoldError> {
+    if line >= size {
+        return Err(FoldError::OutsidePaper { line, size });
+    }
+    let shift = (size - 1 - line).saturating_sub(line);
+
+    let mut map = FoldMap {
+        sources: HashMap::with_capacity(cords.len()),
+        dropped: Vec::new(),
+        // Filled in by the caller, which knows both axes
+        sheet: (0, 0),
+    };
+    use std::cmp::Ordering::*;
+    for cord in cords {
+        let value = get(cord);
+        let new_cord = match value.cmp(&line) {
+            Less => set(cord, value + shift),
+            Greater => set(cord, 2 * line + shift - value),
+            Equal => match line_dots {
+                LineDots::Drop => {
+                    map.dropped.push(*cord);
+                    continue;
+                }
+                LineDots::Reject => return Err(FoldError::DotOnLine(*cord)),
+            },
+        };
+        map.sources.entry(new_cord).or_default().push(*cord);
+    }
+    Ok(map)
+}
+
+/// A fold applied during a session, with the dots left after it
+#[derive(Clone, Debug, PartialEq)]
+struct FoldRecord {
+    fold: FoldDirection,
+    dots: usize,
+    map: FoldMap,
+}
+
+/// Paper being folded one instruction at a time, remembering every fold so
+/// they can be undone or replayed for inspection
+#[derive(Debug)]
+struct FoldSession {
+    origami: PaperOrigami,
+    line_dots: LineDots,
+    history: Vec<FoldRecord>,
+}
+
+impl FoldSession {
+    fn new(origami: PaperOrigami, line_dots: LineDots) -> FoldSession {
+        FoldSession {
+            origami,
+            line_dots,
+            history: Vec::new(),
         }
+    }
+
+    /// Fold the current paper, giving the dots left. A failed fold leaves the
+    /// paper untouched.
+    fn apply(&mut self, fold_dir: FoldDirection) -> Result<usize, FoldError> {
+        let (origami, map) = self.origami.fold_mapped(fold_dir, self.line_dots)?;
+        let dots = origami.cords.len();
+        self.history.push(FoldRecord {
+            fold: fold_dir,
+            dots,
+            map,
+        });
+        self.origami = origami;
+        Ok(dots)
+    }
 
-        PaperOrigami::new(new_cords)
+    /// Undo the last fold, giving it back
+    fn undo(&mut self) -> Option<FoldDirection> {
+        let record = self.history.pop()?;
+        self.origami = self.origami.unfold(&record.map);
+        Some(record.fold)
     }
 
-    fn fold_vertical(&self, fold_row: usize) -> Option<PaperOrigami> {
-        let mut new_cords: HashSet<Coordinate> = HashSet::with_capacity(self.cords.len());
+    /// Every sheet from the unfolded paper through to the current one
+    fn snapshots(&self) -> Vec<PaperOrigami> {
+        let mut sheets = vec![self.origami.clone()];
+        for record in self.history.iter().rev() {
+            let earlier = sheets.last().unwrap().unfold(&record.map);
+            sheets.push(earlier);
+        }
+        sheets.reverse();
+        sheets
+    }
+
+    /// Write every sheet, each headed by the fold that produced it
+    fn write_snapshots<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (index, sheet) in self.snapshots().iter().enumerate() {
+            match index.checked_sub(1).map(|i| &self.history[i]) {
+                None => writeln!(out, "Sheet 0, unfolded")?,
+                Some(record) => writeln!(
+                    out,
+                    "Sheet {}, after {} ({} dots)",
+                    index, record.fold, record.dots
+                )?,
+            }
+            write!(out, "{}", sheet)?;
+        }
+        Ok(())
+    }
+}
 
-        use std::cmp::Ordering::*;
-        for cord in &self.cords {
-            match cord.m.cmp(&fold_row) {
-                Less => {
-                    new_cords.insert(*cord);
+impl PaperOrigami {
+    /// Read the block letters left on the paper
+    fn read_letters(&self) -> Result<String, OcrError> {
+        if self.size_m != GLYPH_HEIGHT {
+            return Err(OcrError::Height(self.size_m));
+        }
+
+        let dot = |m: usize, n: usize| self.paper[m].get(n) == Some(&'#');
+        let count = self.size_n.div_ceil(GLYPH_WIDTH + 1);
+        let mut text = String::with_capacity(count);
+        let mut positions = Vec::new();
+        for glyph in 0..count {
+            let left = glyph * (GLYPH_WIDTH + 1);
+            let spaced = (0..GLYPH_HEIGHT).all(|m| !dot(m, left + GLYPH_WIDTH));
+            let letter = FONT.iter().find(|(_, rows)| {
+                rows.iter().enumerate().all(|(m, row)| {
+                    row.chars()
+                        .enumerate()
+                        .all(|(n, c)| (c == '#') == dot(m, left + n))
+                })
+            });
+
+            match letter {
+                Some((c, _)) if spaced => text.push(*c),
+                _ => {
+                    text.push('?');
+                    positions.push(glyph);
                 }
-                Greater => {
-                    new_cords.insert(Coordinate {
-                        m: cord.m - 2 * (cord.m - fold_row),
-                        n: cord.n,
-                    });
+            }
+        }
+
+        if positions.is_empty() {
+            Ok(text)
+        } else {
+            Err(OcrError::Unrecognised { text, positions })
+        }
+    }
+
+    /// Plain (P1) PBM with each dot drawn as a `cell` pixel square
+    fn to_pbm(&self, cell: usize) -> String {
+        let mut out = format!("P1\n{} {}\n", self.size_n * cell, self.size_m * cell);
+        for line in &self.paper {
+            let row: Vec<char> = line
+                .iter()
+                .flat_map(|&c| std::iter::repeat_n(if c == '#' { '1' } else { '0' }, cell))
+                .collect();
+            for _ in 0..cell {
+                // Plain PBM lines should stay within 70 characters
+                for chunk in row.chunks(70) {
+                    out.extend(chunk);
+                    out.push('\n');
                 }
-                _ => unreachable!(),
             }
         }
+        out
+    }
 
-        PaperOrigami::new(new_cords)
+    fn write_pbm(&self, cell: usize, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_pbm(cell))
+    }
+
+    /// SVG with each dot drawn as a `cell` unit black square on white
+    fn to_svg(&self, cell: usize) -> String {
+        let (width, height) = (self.size_n * cell, self.size_m * cell);
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            width, height
+        );
+        out.push_str(&format!(
+            "  <rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+            width, height
+        ));
+        for (m, line) in self.paper.iter().enumerate() {
+            for (n, _) in line.iter().enumerate().filter(|&(_, &c)| c == '#') {
+                out.push_str(&format!(
+                    "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"black\"/>\n",
+                    n * cell,
+                    m * cell,
+                    cell,
+                    cell
+                ));
+            }
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    fn write_svg(&self, cell: usize, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_svg(cell))
     }
 }
 
@@ -340,10 +675,10 @@ fn test_daythirteen_paperorigami_example() {
     assert_eq!(origami.size_m, 15);
     assert_eq!(origami.size_n, 11);
 
-    origami = origami.fold_vertical(7).unwrap();
+    origami = origami.fold_vertical(7, LineDots::Reject).unwrap();
     assert_eq!(origami.cords.len(), 17);
 
-    origami = origami.fold_horizontal(5).unwrap();
+    origami = origami.fold_horizontal(5, LineDots::Reject).unwrap();
     assert_eq!(origami.cords.len(), 16);
     assert_eq!(origami.size_m, 5);
     assert_eq!(origami.size_n, 5);
@@ -374,4 +709,264 @@ fn test_daythirteen_paperorigami_actual() {
     assert_eq!(origami.cords.len(), 98);
     assert_eq!(origami.size_m, 6);
     assert_eq!(origami.size_n, 39);
+    assert_eq!(origami.read_letters(), Ok("FJAHJGAH".to_string()));
+}
+
+#[test]
+fn test_daythirteen_read_letters() {
+    let draw = |rows: &[&str]| {
+        let cords = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(m, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == '#')
+                    .map(move |(n, _)| Coordinate { m, n })
+            })
+            .collect();
+        PaperOrigami::new(cords).unwrap()
+    };
+
+    // Trailing blank columns are lost when the paper is rebuilt
+    let hi = draw(&[
+        "#..#..###",
+        "#..#...#.",
+        "####...#.",
+        "#..#...#.",
+        "#..#...#.",
+        "#..#..###",
+    ]);
+    assert_eq!(hi.size_n, 9);
+    assert_eq!(hi.read_letters(), Ok("HI".to_string()));
+
+    let smudged = draw(&[
+        "#..#.#..#.####",
+        "#..#.#..#.#...",
+        "####.#.##.###.",
+        "#..#.#..#.#...",
+        "#..#.#..#.#...",
+        "#..#.#..#.####",
+    ]);
+    assert_eq!(
+        smudged.read_letters(),
+        Err(OcrError::Unrecognised {
+            text: "H?E".to_string(),
+            positions: vec![1]
+        })
+    );
+
+    // Dots joining two letters spoil both
+    let joined = draw(&[
+        "#..#.#..#",
+        "#..#.#..#",
+        "#########",
+        "#..#.#..#",
+        "#..#.#..#",
+        "#..#.#..#",
+    ]);
+    assert_eq!(
+        joined.read_letters(),
+        Err(OcrError::Unrecognised {
+            text: "?H".to_string(),
+            positions: vec![0]
+        })
+    );
+
+    let input = read_input::read_file("day_thirteen_test_input.txt");
+    let seps: Vec<&[String]> = input.split(|x| x.is_empty()).collect();
+    let cords_set: HashSet<Coordinate> = seps[0]
+        .iter()
+        .map(|x| Coordinate::new(x).unwrap())
+        .collect();
+    let origami = PaperOrigami::new(cords_set).unwrap();
+    assert_eq!(origami.read_letters(), Err(OcrError::Height(15)));
+}
+
+#[test]
+fn test_daythirteen_fold_errors() {
+    let paper = |cords: &[(usize, usize)]| {
+        let cords = cords.iter().map(|&(m, n)| Coordinate { m, n }).collect();
+        PaperOrigami::new(cords).unwrap()
+    };
+
+    let origami = paper(&[(0, 0), (2, 3), (4, 4)]);
+    assert_eq!(
+        origami.fold(FoldDirection::Vertical(2)),
+        Err(FoldError::DotOnLine(Coordinate { m: 2, n: 3 }))
+    );
+    let dropped = origami
+        .fold_with(FoldDirection::Vertical(2), LineDots::Drop)
+        .unwrap();
+    assert_eq!(dropped.cords, paper(&[(0, 0), (0, 4)]).cords);
+
+    assert_eq!(
+        origami.fold(FoldDirection::Horizontal(5)),
+        Err(FoldError::OutsidePaper { line: 5, size: 5 })
+    );
+    assert_eq!(
+        paper(&[(1, 1)]).fold_with(FoldDirection::Horizontal(1), LineDots::Drop),
+        Err(FoldError::Empty)
+    );
+}
+
+#[test]
+fn test_daythirteen_fold_off_centre() {
+    let cords = [(0, 0), (0, 1), (0, 4), (0, 6)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let origami = PaperOrigami::new(cords).unwrap();
+
+    // Folding at column 2 reflects 4 onto 0 and 6 onto -2, so everything
+    // moves right by two
+    let folded = origami.fold(FoldDirection::Horizontal(2)).unwrap();
+    let mut ns: Vec<usize> = folded.cords.iter().map(|c| c.n).collect();
+    ns.sort_unstable();
+    assert_eq!(ns, vec![0, 2, 3]);
+    assert_eq!(folded.size_n, 4);
+
+    // And the same again, transposed
+    let cords = [(0, 0), (1, 0), (4, 0), (6, 0)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let folded = PaperOrigami::new(cords)
+        .unwrap()
+        .fold(FoldDirection::Vertical(2))
+        .unwrap();
+    let mut ms: Vec<usize> = folded.cords.iter().map(|c| c.m).collect();
+    ms.sort_unstable();
+    assert_eq!(ms, vec![0, 2, 3]);
+}
+
+#[test]
+fn test_daythirteen_fold_session() {
+    let input = read_input::read_file("day_thirteen_test_input.txt");
+    let seps: Vec<&[String]> = input.split(|x| x.is_empty()).collect();
+    let cords_set: HashSet<Coordinate> = seps[0]
+        .iter()
+        .map(|x| Coordinate::new(x).unwrap())
+        .collect();
+    let original = PaperOrigami::new(cords_set).unwrap();
+
+    let mut session = FoldSession::new(original.clone(), LineDots::Reject);
+    for line in seps[1] {
+        session.apply(FoldDirection::new(line).unwrap()).unwrap();
+    }
+    let dots: Vec<usize> = session.history.iter().map(|r| r.dots).collect();
+    assert_eq!(dots, vec![17, 16]);
+
+    let folded = session.origami.clone();
+    let snapshots = session.snapshots();
+    assert_eq!(snapshots.len(), 3);
+    assert_eq!(snapshots[0], original);
+    assert_eq!(snapshots[1].cords.len(), 17);
+    assert_eq!(snapshots[2], folded);
+
+    let mut dump = Vec::new();
+    session.write_snapshots(&mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.starts_with("Sheet 0, unfolded\nPaper Origami (15x11, 18 Coordinates)"));
+    assert!(dump.contains("Sheet 2, after fold along x=5 (16 dots)\n"));
+
+    // A failed fold records nothing
+    assert_eq!(
+        session.apply(FoldDirection::Vertical(9)),
+        Err(FoldError::OutsidePaper { line: 9, size: 7 })
+    );
+    assert_eq!(session.history.len(), 2);
+
+    assert_eq!(session.undo(), Some(FoldDirection::Horizontal(5)));
+    assert_eq!(session.origami, snapshots[1]);
+    assert_eq!(session.undo(), Some(FoldDirection::Vertical(7)));
+    assert_eq!(session.origami, original);
+    assert_eq!(session.undo(), None);
+}
+
+#[test]
+fn test_daythirteen_fold_session_undo_dropped() {
+    let cords = [(0, 0), (2, 1), (4, 1)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let original = PaperOrigami::new(cords).unwrap();
+
+    let mut session = FoldSession::new(original.clone(), LineDots::Drop);
+    assert_eq!(session.apply(FoldDirection::Vertical(2)), Ok(2));
+    assert_eq!(
+        session.history[0].map.dropped,
+        vec![Coordinate { m: 2, n: 1 }]
+    );
+    assert_eq!(
+        session.history[0].map.sources[&Coordinate { m: 0, n: 1 }],
+        vec![Coordinate { m: 4, n: 1 }]
+    );
+
+    session.undo();
+    assert_eq!(session.origami, original);
+}
+
+#[test]
+fn test_daythirteen_to_pbm() {
+    let cords = [(0, 0), (1, 2)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let origami = PaperOrigami::new(cords).unwrap();
+
+    assert_eq!(origami.to_pbm(1), "P1\n3 2\n100\n001\n");
+    assert_eq!(
+        origami.to_pbm(2),
+        "P1\n6 4\n110000\n110000\n000011\n000011\n"
+    );
+
+    // Wide rows wrap to keep lines short
+    let wide = PaperOrigami::new([Coordinate { m: 0, n: 79 }].into_iter().collect()).unwrap();
+    let pbm = wide.to_pbm(1);
+    let lines: Vec<&str> = pbm.lines().collect();
+    assert_eq!(lines[1], "80 1");
+    assert_eq!(lines[2].len(), 70);
+    assert_eq!(lines[3], "0000000001");
+}
+
+#[test]
+fn test_daythirteen_to_svg() {
+    let cords = [(0, 0), (1, 2)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let origami = PaperOrigami::new(cords).unwrap();
+
+    let svg = origami.to_svg(10);
+    assert!(svg.starts_with(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"30\" height=\"20\" viewBox=\"0 0 30 20\">\n"
+    ));
+    assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"10\" height=\"10\" fill=\"black\"/>"));
+    assert!(svg.contains("<rect x=\"20\" y=\"10\" width=\"10\" height=\"10\" fill=\"black\"/>"));
+    assert_eq!(svg.matches("fill=\"black\"").count(), 2);
+    assert!(svg.ends_with("</svg>\n"));
+}
+
+#[test]
+fn test_daythirteen_fold_blank_far_half() {
+    let cords = [(0, 0), (0, 10)]
+        .iter()
+        .map(|&(m, n)| Coordinate { m, n })
+        .collect();
+    let origami = PaperOrigami::new(cords).unwrap();
+
+    let folded = origami.fold(FoldDirection::Horizontal(5)).unwrap();
+    assert_eq!(folded.size_n, 1);
+    assert_eq!((folded.sheet_m, folded.sheet_n), (1, 5));
+
+    // Past the last dotted column but still on the sheet, so nothing moves
+    let again = folded.fold(FoldDirection::Horizontal(2)).unwrap();
+    assert_eq!(again.cords, folded.cords);
+    assert_eq!(again.sheet_n, 2);
+
+    assert_eq!(
+        again.fold(FoldDirection::Horizontal(3)),
+        Err(FoldError::OutsidePaper { line: 3, size: 2 })
+    );
 }
@@ -3,6 +3,9 @@
 #![allow(dead_code)]
 
 use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io;
 use std::time::Instant;
 
 use crate::read_input;
@@ -21,26 +24,20 @@ pub(crate) fn day_twelve_main() {
 }
 
 fn part_one(path_dict: &HashMap<&str, Vec<&str>>) {
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add(vec!["start".to_string()], val.to_string(), path_dict);
+    match CaveGraph::new(path_dict).count_paths(&VisitPolicy::part_one()) {
+        Some(path_count) => println!("Part One, Path Count: {}", path_count),
+        None => println!("Part One, Unbounded Paths"),
     }
-
-    let path_count = count_paths(&path_tree);
-    println!("Part One, Path Count: {}", path_count);
 }
 
 fn part_two(path_dict: &HashMap<&str, Vec<&str>>) {
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add2(vec!["start".to_string()], val.to_string(), path_dict);
+    match CaveGraph::new(path_dict).count_paths(&VisitPolicy::part_two()) {
+        Some(path_count) => println!("Part Two, Path Count: {}", path_count),
+        None => println!("Part Two, Unbounded Paths"),
     }
-
-    let path_count = count_paths(&path_tree);
-    println!("Part Two, Path Count: {}", path_count);
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum CaveType {
     End,
     Large,
@@ -48,132 +45,534 @@ enum CaveType {
     Start,
 }
 
-#[derive(Debug)]
-struct CaveNode {
-    cave_type: CaveType,
-    id: String,
-    paths: Vec<CaveNode>,
-}
-
-impl CaveNode {
-    fn new(new_id: String) -> CaveNode {
-        match new_id {
-            id if id.eq("start") => CaveNode {
-                cave_type: CaveType::Start,
-                id,
-                paths: Vec::<CaveNode>::new(),
-            },
-            id if id.eq("end") => CaveNode {
-                cave_type: CaveType::End,
-                id,
-                paths: Vec::<CaveNode>::new(),
-            },
-            id if id.chars().next().unwrap().is_lowercase() => CaveNode {
-                cave_type: CaveType::Small,
-                id,
-                paths: Vec::<CaveNode>::new(),
-            },
-            id if id.chars().next().unwrap().is_uppercase() => CaveNode {
-                cave_type: CaveType::Large,
-                id,
-                paths: Vec::<CaveNode>::new(),
-            },
+impl CaveType {
+    fn new(id: &str) -> CaveType {
+        match id {
+            "start" => CaveType::Start,
+            "end" => CaveType::End,
+            id if id.chars().next().unwrap().is_lowercase() => CaveType::Small,
+            id if id.chars().next().unwrap().is_uppercase() => CaveType::Large,
             _ => unreachable!(),
         }
     }
+}
+
+/// Caves interned as indices into their sorted names
+#[derive(Debug)]
+struct CaveGraph {
+    names: Vec<String>,
+    types: Vec<CaveType>,
+    adjacency: Vec<Vec<usize>>,
+    start: usize,
+    end: usize,
+}
+
+impl CaveGraph {
+    fn new(path_dict: &HashMap<&str, Vec<&str>>) -> CaveGraph {
+        let mut names: Vec<String> = path_dict
+            .iter()
+            .flat_map(|(k, v)| std::iter::once(k).chain(v.iter()))
+            .map(|x| x.to_string())
+            .chain(["start".to_string(), "end".to_string()])
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+
+        let id = |name: &str| names.binary_search_by(|x| x.as_str().cmp(name)).unwrap();
+        let mut adjacency = vec![Vec::new(); names.len()];
+        for (from, tos) in path_dict {
+            adjacency[id(from)] = tos.iter().map(|to| id(to)).collect();
+            adjacency[id(from)].sort_unstable();
+        }
+
+        CaveGraph {
+            types: names.iter().map(|x| CaveType::new(x)).collect(),
+            start: id("start"),
+            end: id("end"),
+            names,
+            adjacency,
+        }
+    }
+
+    /// Visit limit of every cave under a policy
+    fn limits(&self, policy: &VisitPolicy) -> Vec<Limit> {
+        self.names
+            .iter()
+            .zip(&self.types)
+            .map(
+                |(name, cave_type)| match (cave_type, policy.overrides.get(name)) {
+                    (CaveType::Start, _) => Limit::Fixed(1),
+                    (CaveType::End, _) => Limit::Unlimited,
+                    (_, Some(&max)) => Limit::Fixed(max),
+                    (CaveType::Large, None) => match policy.large_max {
+                        Some(max) => Limit::Fixed(max),
+                        None => Limit::Unlimited,
+                    },
+                    (CaveType::Small, None) => Limit::Small,
+                },
+            )
+            .collect()
+    }
 
-    /// Add method satisfying the part one constraints.
-    fn add(
-        &mut self,
-        mut chain: Vec<String>,
-        new_id: String,
-        path_dict: &HashMap<&str, Vec<&str>>,
-    ) -> bool {
-        let mut is_term_path = false;
-        let mut new_node = CaveNode::new(new_id.clone());
-
-        match (&self.cave_type, &new_node.cave_type) {
-            (_, &CaveType::Small) if chain.contains(&new_id) => false,
-            (_, &CaveType::End) => {
-                self.paths.push(new_node);
-                true
+    /// Caves on some walk from start to end, ignoring how often caves have
+    /// been visited. Paths never go back into start or into forbidden caves.
+    fn live_caves(&self, limits: &[Limit]) -> Vec<bool> {
+        let passable = |cave: usize| cave != self.start && limits[cave] != Limit::Fixed(0);
+        let mut reverse = vec![Vec::new(); self.names.len()];
+        for (from, tos) in self.adjacency.iter().enumerate() {
+            for &to in tos {
+                reverse[to].push(from);
             }
-            _ => {
-                chain.push(new_id.clone());
-                if let Some(values) = path_dict.get(&new_id.as_str()) {
-                    for val in values {
-                        is_term_path |= new_node.add(chain.clone(), val.to_string(), path_dict);
+        }
+
+        let reach = |from: usize, adjacency: &[Vec<usize>]| {
+            let mut seen = vec![false; self.names.len()];
+            seen[from] = true;
+            let mut queue = vec![from];
+            while let Some(cave) = queue.pop() {
+                for &next in &adjacency[cave] {
+                    if !seen[next] && (passable(next) || next == self.start) {
+                        seen[next] = true;
+                        queue.push(next);
                     }
                 }
+            }
+            seen
+        };
+        let from_start = reach(self.start, &self.adjacency);
+        let to_end = reach(self.end, &reverse);
+
+        from_start
+            .iter()
+            .zip(&to_end)
+            .map(|(&a, &b)| a && b)
+            .collect()
+    }
 
-                if is_term_path {
-                    self.paths.push(new_node);
-                }
-                is_term_path
+    /// Whether two joined caves without limits, both on some walk from start
+    /// to end, allow endlessly long paths
+    fn is_unbounded(&self, limits: &[Limit]) -> bool {
+        let live = self.live_caves(limits);
+        let looping =
+            |cave: usize| live[cave] && limits[cave] == Limit::Unlimited && cave != self.end;
+        self.adjacency
+            .iter()
+            .enumerate()
+            .any(|(from, tos)| looping(from) && tos.iter().any(|&to| looping(to)))
+    }
+
+    /// Paths from start to end allowed by `policy`. Gives `None` when two
+    /// joined caves without limits make the count infinite.
+    fn count_paths(&self, policy: &VisitPolicy) -> Option<u64> {
+        let limits = self.limits(policy);
+        if self.is_unbounded(&limits) {
+            return None;
+        }
+
+        Some(PathCounter::new(self, policy, limits).count())
+    }
+
+    /// Lazily list the paths allowed by `policy`, in order of cave names at
+    /// each step. Gives `None` when there are infinitely many.
+    fn paths(&self, policy: &VisitPolicy) -> Option<Paths<'_>> {
+        let limits = self.limits(policy);
+        if self.is_unbounded(&limits) {
+            return None;
+        }
+
+        let mut visits = vec![0_u8; self.names.len()];
+        visits[self.start] = 1;
+        Some(Paths {
+            graph: self,
+            policy: policy.clone(),
+            live: self.live_caves(&limits),
+            limits,
+            visits,
+            path: vec![self.start],
+            stack: vec![(0, 0)],
+            through: Vec::new(),
+            remaining: None,
+        })
+    }
+
+    /// Every passage as the pair of caves with the lower index first
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges: Vec<(usize, usize)> = self
+            .adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(from, tos)| tos.iter().map(move |&to| (from.min(to), from.max(to))))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    /// How many paths allowed by `policy` use each passage at least once,
+    /// found as every path less those left when the passage is closed
+    fn edge_traffic(&self, policy: &VisitPolicy) -> Option<HashMap<(usize, usize), u64>> {
+        let limits = self.limits(policy);
+        if self.is_unbounded(&limits) {
+            return None;
+        }
+
+        let total = PathCounter::new(self, policy, limits.clone()).count();
+        let traffic = self
+            .edges()
+            .into_iter()
+            .map(|edge| {
+                let mut counter = PathCounter::new(self, policy, limits.clone());
+                counter.closed = Some(edge);
+                (edge, total - counter.count())
+            })
+            .collect();
+        Some(traffic)
+    }
+
+    /// Graphviz DOT of the cave system, styled by cave type, with each passage
+    /// labelled by the number of paths under `policy` that use it. Gives `None`
+    /// when there are infinitely many paths.
+    fn to_dot(&self, policy: &VisitPolicy) -> Option<String> {
+        let traffic = self.edge_traffic(policy)?;
+
+        let mut out = String::from("graph caves {\n");
+        for (name, cave_type) in self.names.iter().zip(&self.types) {
+            let style = match cave_type {
+                CaveType::Start => "shape=invhouse, style=filled, fillcolor=palegreen",
+                CaveType::End => "shape=house, style=filled, fillcolor=lightcoral",
+                CaveType::Large => "shape=box, style=bold",
+                CaveType::Small => "shape=ellipse",
+            };
+            out.push_str(&format!("    \"{}\" [{}];\n", name, style));
+        }
+
+        for (a, b) in self.edges() {
+            let weight = traffic.get(&(a, b)).copied().unwrap_or(0);
+            out.push_str(&format!(
+                "    \"{}\" -- \"{}\" [label=\"{}\", weight={}];\n",
+                self.names[a], self.names[b], weight, weight
+            ));
+        }
+        out.push_str("}\n");
+        Some(out)
+    }
+
+    fn write_dot(&self, policy: &VisitPolicy, path: &str) -> io::Result<()> {
+        match self.to_dot(policy) {
+            Some(dot) => fs::write(path, dot),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unbounded paths",
+            )),
+        }
+    }
+}
+
+/// Visits to the caves with a limit, as memoized by `PathCounter`
+trait VisitState: Clone + Eq + Hash {
+    fn visits(&self, slot: &Slot) -> u8;
+
+    /// The state after one more visit to the cave in `slot`
+    fn visit(&self, slot: &Slot) -> Self;
+}
+
+/// Where a limited cave's visit count lives, as an index into a `Vec<u8>` or a
+/// bit range of a packed `u128`
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    index: usize,
+    offset: u32,
+    width: u32,
+}
+
+impl VisitState for u128 {
+    fn visits(&self, slot: &Slot) -> u8 {
+        ((self >> slot.offset) & ((1 << slot.width) - 1)) as u8
+    }
+
+    fn visit(&self, slot: &Slot) -> u128 {
+        self + (1 << slot.offset)
+    }
+}
+
+impl VisitState for Vec<u8> {
+    fn visits(&self, slot: &Slot) -> u8 {
+        self[slot.index]
+    }
+
+    fn visit(&self, slot: &Slot) -> Vec<u8> {
+        let mut next = self.clone();
+        next[slot.index] += 1;
+        next
+    }
+}
+
+/// Memoized path counting. Only caves with a limit have their visits tracked,
+/// so states differing just in visits to unlimited caves share a memo entry.
+/// The counts are packed into a `u128` when they fit, each cave taking just
+/// enough bits for its largest allowed count, and kept in a `Vec<u8>` when not.
+struct PathCounter<'a> {
+    graph: &'a CaveGraph,
+    policy: &'a VisitPolicy,
+    limits: Vec<Limit>,
+    /// Caves from which end can still be reached, the rest being dead ends
+    live: Vec<bool>,
+    /// Where each cave's visit count is kept, `None` when untracked
+    slots: Vec<Option<Slot>>,
+    /// Bits needed to pack every tracked count
+    packed_width: u32,
+    /// Passage that may not be used, lower cave index first
+    closed: Option<(usize, usize)>,
+}
+
+impl<'a> PathCounter<'a> {
+    fn new(graph: &'a CaveGraph, policy: &'a VisitPolicy, limits: Vec<Limit>) -> PathCounter<'a> {
+        let mut index = 0;
+        let mut offset = 0;
+        let slots = limits
+            .iter()
+            .map(|limit| {
+                let max = match limit {
+                    Limit::Unlimited | Limit::Fixed(0) => return None,
+                    Limit::Fixed(max) => *max,
+                    Limit::Small => policy.small_max.max(1),
+                };
+                let slot = Slot {
+                    index,
+                    offset,
+                    width: u8::BITS - max.leading_zeros(),
+                };
+                index += 1;
+                offset += slot.width;
+                Some(slot)
+            })
+            .collect();
+
+        PathCounter {
+            graph,
+            policy,
+            live: graph.live_caves(&limits),
+            limits,
+            slots,
+            packed_width: offset,
+            closed: None,
+        }
+    }
+
+    /// Paths from start to end
+    fn count(&self) -> u64 {
+        if self.packed_width <= u128::BITS {
+            self.count_with(0_u128)
+        } else {
+            self.count_with(vec![0_u8; self.slots.iter().flatten().count()])
+        }
+    }
+
+    fn count_with<S: VisitState>(&self, empty: S) -> u64 {
+        let start = self.graph.start;
+        let visited = self.visit(&empty, start);
+        self.count_from(start, visited, 0, &mut HashMap::new())
+    }
+
+    fn visits<S: VisitState>(&self, visited: &S, cave: usize) -> u8 {
+        match &self.slots[cave] {
+            Some(slot) => visited.visits(slot),
+            None => 0,
+        }
+    }
+
+    /// Record one more visit to `cave`, which the limits allow
+    fn visit<S: VisitState>(&self, visited: &S, cave: usize) -> S {
+        match &self.slots[cave] {
+            Some(slot) => visited.visit(slot),
+            None => visited.clone(),
+        }
+    }
+
+    fn count_from<S: VisitState>(
+        &self,
+        cave: usize,
+        visited: S,
+        exceeded: u8,
+        memo: &mut HashMap<(usize, S, u8), u64>,
+    ) -> u64 {
+        if cave == self.graph.end {
+            return 1;
+        }
+        let key = (cave, visited, exceeded);
+        if let Some(&count) = memo.get(&key) {
+            return count;
+        }
+        let visited = &key.1;
+
+        let mut count = 0;
+        for &next in &self.graph.adjacency[cave] {
+            if !self.live[next] || self.closed == Some((cave.min(next), cave.max(next))) {
+                continue;
+            }
+            let visits = self.visits(visited, next);
+            if let Some(exceeded) = self.policy.enter(self.limits[next], visits, exceeded) {
+                count += self.count_from(next, self.visit(visited, next), exceeded, memo);
             }
         }
+
+        memo.insert(key, count);
+        count
     }
+}
 
-    /// Add method satisfying the part two constraints.
-    fn add2(
-        &mut self,
-        mut chain: Vec<String>,
-        new_id: String,
-        path_dict: &HashMap<&str, Vec<&str>>,
-    ) -> bool {
-        let mut is_term_path = false;
-        let mut new_node = CaveNode::new(new_id.clone());
-
-        match (&self.cave_type, &new_node.cave_type) {
-            (_, &CaveType::Small) if chain.contains(&new_id) => {
-                let mut is_doubled = false;
-                for lower in chain
-                    .iter()
-                    .filter(|s| s.chars().next().unwrap().is_lowercase())
-                {
-                    if chain.iter().filter(|s| s.eq(&lower)).count() > 1 {
-                        is_doubled = true;
-                        break;
-                    }
-                }
+/// Depth first walk over the paths of a cave graph, one path per `next`
+#[derive(Debug)]
+struct Paths<'a> {
+    graph: &'a CaveGraph,
+    policy: VisitPolicy,
+    limits: Vec<Limit>,
+    /// Caves from which end can still be reached
+    live: Vec<bool>,
+    visits: Vec<u8>,
+    /// Caves on the current partial path
+    path: Vec<usize>,
+    /// For each cave on the path, the next neighbour to try and the count of
+    /// small caves past one visit on arrival
+    stack: Vec<(usize, u8)>,
+    /// Caves every yielded path must pass through
+    through: Vec<usize>,
+    /// Paths left to yield, `None` for no limit
+    remaining: Option<usize>,
+}
 
-                if !is_doubled {
-                    chain.push(new_id.clone());
-                    if let Some(values) = path_dict.get(&new_id.as_str()) {
-                        for val in values {
-                            is_term_path |=
-                                new_node.add2(chain.clone(), val.to_string(), path_dict);
-                        }
-                    }
+impl<'a> Paths<'a> {
+    /// Only yield paths passing through `cave`, of which there are none if no
+    /// such cave exists
+    fn through(mut self, cave: &str) -> Paths<'a> {
+        match self.graph.names.iter().position(|x| x == cave) {
+            Some(id) => self.through.push(id),
+            None => self.remaining = Some(0),
+        }
+        self
+    }
 
-                    if is_term_path {
-                        self.paths.push(new_node);
-                    }
-                }
+    /// Stop after yielding `count` paths
+    fn limit(mut self, count: usize) -> Paths<'a> {
+        self.remaining = Some(self.remaining.map_or(count, |r| r.min(count)));
+        self
+    }
 
-                is_term_path
+    /// Next path as cave indices
+    fn next_ids(&mut self) -> Option<Vec<usize>> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        let graph = self.graph;
+        loop {
+            let cave = *self.path.last()?;
+            let frame = self.stack.last_mut()?;
+            let Some(&next) = graph.adjacency[cave].get(frame.0) else {
+                self.stack.pop();
+                self.path.pop();
+                self.visits[cave] -= 1;
+                continue;
+            };
+            frame.0 += 1;
+            if !self.live[next] {
+                continue;
             }
-            (_, &CaveType::End) => {
-                self.paths.push(new_node);
-                true
+
+            let Some(exceeded) = self
+                .policy
+                .enter(self.limits[next], self.visits[next], frame.1)
+            else {
+                continue;
+            };
+            if next != graph.end {
+                self.visits[next] += 1;
+                self.path.push(next);
+                self.stack.push((0, exceeded));
+                continue;
             }
-            _ => {
-                chain.push(new_id.clone());
-                if let Some(values) = path_dict.get(&new_id.as_str()) {
-                    for val in values {
-                        is_term_path |= new_node.add2(chain.clone(), val.to_string(), path_dict);
-                    }
-                }
 
-                if is_term_path {
-                    self.paths.push(new_node);
-                }
-                is_term_path
+            // Every path ends at end, which is never counted into visits
+            if self
+                .through
+                .iter()
+                .all(|&id| id == graph.end || self.visits[id] > 0)
+            {
+                self.remaining = self.remaining.map(|r| r - 1);
+                return Some(self.path.iter().copied().chain([graph.end]).collect());
             }
         }
     }
 }
 
+impl<'a> Iterator for Paths<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Vec<&'a str>> {
+        let graph = self.graph;
+        let ids = self.next_ids()?;
+        Some(ids.iter().map(|&id| graph.names[id].as_str()).collect())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Limit {
+    Unlimited,
+    Fixed(u8),
+    /// Once, or up to the policy's small cave maximum while few enough small
+    /// caves have gone past once
+    Small,
+}
+
+/// Rules for how often caves may be visited along a single path
+#[derive(Clone, Debug)]
+struct VisitPolicy {
+    /// Most visits to a small cave that goes past one
+    small_max: u8,
+    /// How many small caves may go past one visit
+    small_exceed: u8,
+    /// Fixed visit limits for caves by name, ignoring the small cave rules
+    overrides: HashMap<String, u8>,
+    /// Most visits to each large cave, `None` for no limit
+    large_max: Option<u8>,
+}
+
+impl VisitPolicy {
+    /// Small caves at most once
+    fn part_one() -> VisitPolicy {
+        VisitPolicy {
+            small_max: 1,
+            small_exceed: 0,
+            overrides: HashMap::new(),
+            large_max: None,
+        }
+    }
+
+    /// A single small cave may be visited twice
+    fn part_two() -> VisitPolicy {
+        VisitPolicy {
+            small_max: 2,
+            small_exceed: 1,
+            ..VisitPolicy::part_one()
+        }
+    }
+
+    /// Whether a cave visited `visits` times already may be entered again,
+    /// giving the updated count of small caves past one visit if so
+    fn enter(&self, limit: Limit, visits: u8, exceeded: u8) -> Option<u8> {
+        match limit {
+            Limit::Unlimited => Some(exceeded),
+            Limit::Fixed(max) if visits < max => Some(exceeded),
+            Limit::Fixed(_) => None,
+            Limit::Small if visits == 0 => Some(exceeded),
+            Limit::Small if visits >= self.small_max => None,
+            Limit::Small if visits == 1 && exceeded < self.small_exceed => Some(exceeded + 1),
+            Limit::Small if visits > 1 => Some(exceeded),
+            Limit::Small => None,
+        }
+    }
+}
+
 fn build_path_dict(lines: &[String]) -> HashMap<&str, Vec<&str>> {
     let mut path_dict: HashMap<&str, Vec<&str>> = HashMap::new();
 
@@ -208,20 +607,6 @@ fn build_path_dict(lines: &[String]) -> HashMap<&str, Vec<&str>> {
     path_dict
 }
 
-fn count_paths(cave_node: &CaveNode) -> u32 {
-    let mut path_count = 0_u32;
-
-    match cave_node.cave_type {
-        CaveType::End => 1,
-        _ => {
-            for node in &cave_node.paths {
-                path_count += count_paths(node)
-            }
-            path_count
-        }
-    }
-}
-
 #[test]
 fn test_daytwelve_build_path_dict_sample_small() {
     let input = read_input::read_file("day_twelve_test_input_small.txt");
@@ -240,13 +625,8 @@ fn test_daytwelve_part_one_sample_small() {
     let input = read_input::read_file("day_twelve_test_input_small.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 10)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_one());
+    assert_eq!(paths, Some(10))
 }
 
 #[test]
@@ -254,13 +634,8 @@ fn test_daytwelve_part_one_sample_medium() {
     let input = read_input::read_file("day_twelve_test_input_medium.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 19)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_one());
+    assert_eq!(paths, Some(19))
 }
 
 #[test]
@@ -268,13 +643,8 @@ fn test_daytwelve_part_one_sample_large() {
     let input = read_input::read_file("day_twelve_test_input_large.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 226)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_one());
+    assert_eq!(paths, Some(226))
 }
 
 #[test]
@@ -282,13 +652,8 @@ fn test_daytwelve_part_one_actual() {
     let input = read_input::read_file("day_twelve_input.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 4707)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_one());
+    assert_eq!(paths, Some(4707))
 }
 
 #[test]
@@ -296,13 +661,8 @@ fn test_daytwelve_part_two_sample_small() {
     let input = read_input::read_file("day_twelve_test_input_small.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add2(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 36)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_two());
+    assert_eq!(paths, Some(36))
 }
 
 #[test]
@@ -310,13 +670,8 @@ fn test_daytwelve_part_two_sample_medium() {
     let input = read_input::read_file("day_twelve_test_input_medium.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add2(vec!["start".to_string()], val.to_string(), &path_dict);
-    }
-
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 103)
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_two());
+    assert_eq!(paths, Some(103))
 }
 
 #[test]
@@ -324,11 +679,254 @@ fn test_daytwelve_part_two_sample_large() {
     let input = read_input::read_file("day_twelve_test_input_large.txt");
     let path_dict = build_path_dict(&input);
 
-    let mut path_tree = CaveNode::new("start".to_string());
-    for val in path_dict.get("start").unwrap() {
-        path_tree.add2(vec!["start".to_string()], val.to_string(), &path_dict);
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_two());
+    assert_eq!(paths, Some(3509))
+}
+
+#[test]
+fn test_daytwelve_part_two_actual() {
+    let input = read_input::read_file("day_twelve_input.txt");
+    let path_dict = build_path_dict(&input);
+
+    let paths = CaveGraph::new(&path_dict).count_paths(&VisitPolicy::part_two());
+    assert_eq!(paths, Some(130493))
+}
+
+#[test]
+fn test_daytwelve_count_paths_unbounded() {
+    let input: Vec<String> = ["start-A", "A-B", "B-end"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    let path_dict = build_path_dict(&input);
+
+    let graph = CaveGraph::new(&path_dict);
+    assert_eq!(graph.count_paths(&VisitPolicy::part_one()), None);
+
+    // Capping either large cave bounds the bouncing
+    let mut policy = VisitPolicy::part_one();
+    policy.overrides.insert("B".to_string(), 2);
+    assert_eq!(graph.count_paths(&policy), Some(2));
+
+    let policy = VisitPolicy {
+        large_max: Some(2),
+        ..VisitPolicy::part_one()
+    };
+    assert_eq!(graph.count_paths(&policy), Some(2));
+}
+
+#[test]
+fn test_daytwelve_count_paths_policies() {
+    let input = read_input::read_file("day_twelve_test_input_small.txt");
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+
+    assert_eq!(graph.names, vec!["A", "b", "c", "d", "end", "start"]);
+    assert_eq!(graph.types[graph.start], CaveType::Start);
+
+    // Two small caves twice, or one small cave three times
+    let two_twice = VisitPolicy {
+        small_exceed: 2,
+        ..VisitPolicy::part_two()
+    };
+    let one_thrice = VisitPolicy {
+        small_max: 3,
+        ..VisitPolicy::part_two()
+    };
+    assert_eq!(graph.count_paths(&VisitPolicy::part_two()), Some(36));
+    assert_eq!(graph.count_paths(&two_twice), Some(54));
+    assert_eq!(graph.count_paths(&one_thrice), Some(83));
+
+    // Only b may be visited twice, without using up the shared allowance
+    let mut b_twice = VisitPolicy::part_one();
+    b_twice.overrides.insert("b".to_string(), 2);
+    let mut b_twice_and_one_more = VisitPolicy::part_two();
+    b_twice_and_one_more.overrides.insert("b".to_string(), 2);
+    assert_eq!(graph.count_paths(&b_twice), Some(30));
+    assert_eq!(graph.count_paths(&b_twice_and_one_more), Some(54));
+
+    // Forbidding A leaves only start,b,end
+    let mut no_a = VisitPolicy::part_one();
+    no_a.overrides.insert("A".to_string(), 0);
+    assert_eq!(graph.count_paths(&no_a), Some(1));
+
+    let once_large = VisitPolicy {
+        large_max: Some(1),
+        ..VisitPolicy::part_one()
+    };
+    assert_eq!(graph.count_paths(&once_large), Some(4));
+}
+
+#[test]
+fn test_daytwelve_paths() {
+    let input = read_input::read_file("day_twelve_test_input_small.txt");
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+
+    let paths: Vec<String> = graph
+        .paths(&VisitPolicy::part_one())
+        .unwrap()
+        .map(|p| p.join(","))
+        .collect();
+    assert_eq!(paths.len(), 10);
+    assert_eq!(paths[0], "start,A,b,A,c,A,end");
+    assert_eq!(paths[9], "start,b,end");
+
+    let through_c: Vec<String> = graph
+        .paths(&VisitPolicy::part_one())
+        .unwrap()
+        .through("c")
+        .limit(3)
+        .map(|p| p.join(","))
+        .collect();
+    assert_eq!(
+        through_c,
+        vec![
+            "start,A,b,A,c,A,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end"
+        ]
+    );
+
+    let through_c_and_b = graph
+        .paths(&VisitPolicy::part_one())
+        .unwrap()
+        .through("c")
+        .through("b");
+    assert_eq!(through_c_and_b.count(), 4);
+    let through_ends = graph
+        .paths(&VisitPolicy::part_one())
+        .unwrap()
+        .through("start")
+        .through("end");
+    assert_eq!(through_ends.count(), 10);
+    let missing = graph.paths(&VisitPolicy::part_one()).unwrap().through("x");
+    assert_eq!(missing.count(), 0);
+
+    let part_two = graph.paths(&VisitPolicy::part_two()).unwrap();
+    assert_eq!(part_two.count(), 36);
+}
+
+#[test]
+fn test_daytwelve_paths_match_count() {
+    let input = read_input::read_file("day_twelve_test_input_large.txt");
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+
+    let policy = VisitPolicy::part_two();
+    let paths = graph.paths(&policy).unwrap();
+    assert_eq!(paths.count() as u64, graph.count_paths(&policy).unwrap());
+
+    let input: Vec<String> = ["start-A", "A-B", "B-end"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    let path_dict = build_path_dict(&input);
+    assert!(
+        CaveGraph::new(&path_dict)
+            .paths(&VisitPolicy::part_one())
+            .is_none()
+    );
+}
+
+#[test]
+fn test_daytwelve_to_dot() {
+    let input = read_input::read_file("day_twelve_test_input_small.txt");
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+
+    let dot = graph.to_dot(&VisitPolicy::part_one()).unwrap();
+    assert!(dot.starts_with("graph caves {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains("\"start\" [shape=invhouse"));
+    assert!(dot.contains("\"end\" [shape=house"));
+    assert!(dot.contains("\"A\" [shape=box"));
+    assert!(dot.contains("\"d\" [shape=ellipse]"));
+
+    // Every path starts through A or b, and d is a dead end
+    assert!(dot.contains("\"A\" -- \"start\" [label=\"7\", weight=7];"));
+    assert!(dot.contains("\"b\" -- \"start\" [label=\"3\", weight=3];"));
+    assert!(dot.contains("\"b\" -- \"d\" [label=\"0\", weight=0];"));
+    assert_eq!(dot.matches(" -- ").count(), 7);
+
+    let input: Vec<String> = ["start-A", "A-B", "B-end"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    let path_dict = build_path_dict(&input);
+    assert!(
+        CaveGraph::new(&path_dict)
+            .to_dot(&VisitPolicy::part_one())
+            .is_none()
+    );
+}
+
+#[test]
+fn test_daytwelve_edge_traffic_matches_paths() {
+    let input = read_input::read_file("day_twelve_test_input_large.txt");
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+    let policy = VisitPolicy::part_two();
+
+    let mut enumerated: HashMap<(usize, usize), u64> = HashMap::new();
+    let mut paths = graph.paths(&policy).unwrap();
+    while let Some(path) = paths.next_ids() {
+        let mut edges: Vec<(usize, usize)> = path
+            .windows(2)
+            .map(|w| (w[0].min(w[1]), w[0].max(w[1])))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        for edge in edges {
+            *enumerated.entry(edge).or_insert(0) += 1;
+        }
     }
 
-    let paths = count_paths(&path_tree);
-    assert_eq!(paths, 3509)
+    let traffic = graph.edge_traffic(&policy).unwrap();
+    assert_eq!(traffic.len(), graph.edges().len());
+    for (edge, count) in traffic {
+        assert_eq!(enumerated.get(&edge).copied().unwrap_or(0), count);
+    }
+}
+
+#[test]
+fn test_daytwelve_count_paths_many_caves() {
+    // Too many small caves to pack their visits into a u128
+    let input: Vec<String> = (0..140)
+        .flat_map(|i| [format!("start-c{i}"), format!("c{i}-end")])
+        .collect();
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+
+    assert_eq!(graph.count_paths(&VisitPolicy::part_one()), Some(140));
+    assert_eq!(graph.count_paths(&VisitPolicy::part_two()), Some(140));
+}
+
+#[test]
+fn test_daytwelve_count_paths_dead_loops() {
+    // X and Y loop forever, but can't be reached from start or can't reach end
+    let input: Vec<String> = ["start-A", "A-end", "d-X", "X-Y", "start-P", "P-Q"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+    let policy = VisitPolicy::part_one();
+
+    assert_eq!(graph.count_paths(&policy), Some(1));
+    assert_eq!(graph.paths(&policy).unwrap().count(), 1);
+    let dot = graph.to_dot(&policy).unwrap();
+    assert!(dot.contains("\"P\" -- \"start\" [label=\"0\", weight=0];"));
+
+    // Forbidding the only way round a loop bounds it too
+    let input: Vec<String> = ["start-A", "A-B", "B-c", "c-end"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    let path_dict = build_path_dict(&input);
+    let graph = CaveGraph::new(&path_dict);
+    assert_eq!(graph.count_paths(&policy), None);
+    let mut no_c = VisitPolicy::part_one();
+    no_c.overrides.insert("c".to_string(), 0);
+    assert_eq!(graph.count_paths(&no_c), Some(0));
 }
@@ -1,3 +1,4 @@
+use std::env;
 use std::time::Instant;
 
 mod day_eight;
@@ -16,9 +17,23 @@ mod day_three;
 mod day_twelve;
 mod day_two;
 
+mod automaton;
 mod read_input;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("lint") => {
+            day_ten::lint_main(&args[1..]);
+            return;
+        }
+        Some("animate") => {
+            day_eleven::animate_main(&args[1..]);
+            return;
+        }
+        _ => (),
+    }
+
     println!("Advent of Code - 2021");
     let now = Instant::now();
 